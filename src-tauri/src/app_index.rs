@@ -0,0 +1,1076 @@
+use crate::commands::Application;
+use crate::config::{CyberdeckConfig, APP_CONFIG};
+use crate::config_file;
+use crate::history::{self, LaunchHistory, Launches};
+use crate::icons;
+use crate::paths;
+use crate::stable_hash::StableHasher;
+#[cfg(debug_assertions)]
+use log::debug;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use ts_rs::TS;
+
+/// Shared by the scan command and the directory watcher, loaded from disk on first use.
+/// A missing or unreadable index only means every file is parsed.
+pub static APP_INDEX: Lazy<Mutex<AppIndex>> = Lazy::new(|| {
+    let (header, format) = match APP_CONFIG.lock() {
+        Ok(config) => (IndexHeader::new(&config), config.cache.format),
+        Err(e) => {
+            error!("Failed to lock config: {e}");
+            return Mutex::new(AppIndex::default());
+        }
+    };
+    Mutex::new(AppIndex::load(&header, format).unwrap_or_else(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            // the first start with an index, or the cache was cleared
+            remove_legacy_cache();
+        }
+        warn!("Failed to read the application index, rebuilding it: {e}");
+        AppIndex::default()
+    }))
+});
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/CacheFormat.ts")]
+pub enum CacheFormat {
+    /// `apps_index.json`, readable and easy to inspect
+    Json,
+    /// `apps_index.bin`, compact bincode that loads faster
+    Binary,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/types/CacheConfig.ts")]
+pub struct CacheConfig {
+    /// Format the application index is stored in
+    pub format: CacheFormat,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            format: CacheFormat::Json,
+        }
+    }
+}
+
+/// Bumped whenever `Application`, the index layout or the parsing rules change,
+/// older indexes are rebuilt.
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+/// What an index was built with. When any of it differs from the running
+/// launcher, the index is discarded on load or reparsed on the next rescan.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+pub struct IndexHeader {
+    pub format_version: u32,
+    pub cyberdeck_version: String,
+    /// hash of the settings the applications were parsed with
+    pub config_hash: String,
+    pub locale: String,
+    /// icons were resolved for this window scale factor
+    pub scale_factor: u32,
+}
+
+impl IndexHeader {
+    pub fn new(config: &CyberdeckConfig) -> Self {
+        // everything the parsed applications depend on besides the desktop files
+        let mut hasher = StableHasher::new();
+        hasher.write_field(&config.desktop_environment);
+        hasher.write_field(&config.terminal_app);
+        hasher.write_field(&config.icon_theme);
+        hasher.write_field(config.ui.icon_size.to_le_bytes());
+        for lookup_dir in config.lookup_dirs.resolve() {
+            hasher.write_field(lookup_dir);
+        }
+
+        IndexHeader {
+            format_version: INDEX_FORMAT_VERSION,
+            cyberdeck_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_hash: format!("{:016x}", hasher.finish()),
+            locale: current_locale(),
+            scale_factor: icons::scale_factor(),
+        }
+    }
+
+    // why an index with this header can't be used in place of `current`
+    fn mismatch(&self, current: &IndexHeader) -> Option<String> {
+        if self.format_version != current.format_version {
+            Some(format!(
+                "format version {} differs from {}",
+                self.format_version, current.format_version
+            ))
+        } else if self.cyberdeck_version != current.cyberdeck_version {
+            Some(format!(
+                "built by Cyberdeck {} instead of {}",
+                self.cyberdeck_version, current.cyberdeck_version
+            ))
+        } else if self.config_hash != current.config_hash {
+            Some(String::from("the config changed"))
+        } else if self.locale != current.locale {
+            Some(format!(
+                "locale {} differs from {}",
+                self.locale, current.locale
+            ))
+        } else if self.scale_factor != current.scale_factor {
+            Some(format!(
+                "icons were resolved at {}x instead of {}x",
+                self.scale_factor, current.scale_factor
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+// the locale that decides the language of names and comments, like gettext picks it
+fn current_locale() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|variable| env::var(variable).ok())
+        .find(|locale| !locale.is_empty())
+        .unwrap_or_else(|| String::from("C"))
+}
+
+// The header is stored ahead of the index, a JSON line or bincode, so an index
+// of another format or config is rejected without decoding the applications
+
+fn decode_json_header(line: &[u8]) -> std::io::Result<IndexHeader> {
+    serde_json::from_slice(line)
+        .map_err(|e| std::io::Error::other(format!("Invalid index header: {e}")))
+}
+
+/// The header of the stored index, without reading the applications.
+pub fn read_header(format: CacheFormat) -> std::io::Result<IndexHeader> {
+    let mut reader = BufReader::new(fs::File::open(get_index_path(format)?)?);
+    match format {
+        CacheFormat::Json => {
+            let mut line = vec![];
+            reader.read_until(b'\n', &mut line)?;
+            decode_json_header(&line)
+        }
+        CacheFormat::Binary => {
+            bincode::serde::decode_from_std_read(&mut reader, bincode::config::standard())
+                .map_err(|e| std::io::Error::other(format!("Invalid index header: {e}")))
+        }
+    }
+}
+
+// a desktop file found below the lookup directories
+struct FoundFile {
+    path: PathBuf,
+    /// see `desktop_file_id`
+    id: String,
+    modified: SystemTime,
+    size: u64,
+    /// the file with the same desktop file id that is used instead
+    shadowed_by: Option<PathBuf>,
+}
+
+/// The desktop file id, the path below the lookup directory with `/` replaced
+/// by `-`, e.g. `kde-konsole.desktop` for `kde/konsole.desktop`.
+pub fn desktop_file_id(lookup_dir: &Path, file: &Path) -> Option<String> {
+    let relative = file.strip_prefix(lookup_dir).ok()?;
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    Some(components.join("-"))
+}
+
+/// Every desktop file below `lookup_dirs` in the order they are listed, with its
+/// desktop file id and the file that has the same id and is used instead.
+/// Lookup directories below `$XDG_DATA_HOME` take precedence, then earlier ones.
+pub fn find_desktop_files(lookup_dirs: &[String]) -> Vec<(PathBuf, String, Option<PathBuf>)> {
+    let (found_files, _) = AppIndex::default().walk(lookup_dirs);
+    found_files
+        .into_iter()
+        .map(|file| (file.path, file.id, file.shadowed_by))
+        .collect()
+}
+
+/// Parsed desktop files of the lookup directories, persisted between runs
+/// so a rescan only parses the files that changed.
+#[derive(Serialize, Deserialize, Default)]
+pub struct AppIndex {
+    /// stored separately, ahead of the rest
+    #[serde(skip)]
+    header: IndexHeader,
+    directories: BTreeMap<PathBuf, IndexedDirectory>,
+    files: BTreeMap<PathBuf, IndexedFile>,
+    /// application id -> its launches, a copy of the history so the frecency
+    /// is read along with the applications
+    launches: BTreeMap<String, Launches>,
+}
+
+// the entries of a directory only change along with its modification time,
+// so an unchanged directory isn't read again
+#[derive(Serialize, Deserialize)]
+struct IndexedDirectory {
+    modified: SystemTime,
+    desktop_files: Vec<PathBuf>,
+    subdirectories: Vec<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    modified: SystemTime,
+    size: u64,
+    /// `None` for desktop files that aren't shown, so they aren't parsed again either
+    application: Option<Application>,
+    /// whether the last scan listed the application, see `Application::is_installed`
+    listed: bool,
+}
+
+impl IndexedFile {
+    fn listed_application(&self) -> Option<&Application> {
+        self.application.as_ref().filter(|_| self.listed)
+    }
+}
+
+// how a found desktop file is handled by a rescan
+#[derive(Clone, Copy, PartialEq)]
+enum FileState {
+    /// unchanged since the last scan, taken from the index
+    Unchanged,
+    /// unchanged, but its icon wasn't found, parsed again in case it was installed since
+    IconMissing,
+    /// new or modified
+    Changed,
+}
+
+/// Desktop files handled at a time, see `AppIndex::rescan`.
+pub const SCAN_BATCH_SIZE: usize = 64;
+
+/// What a rescan changed in the index.
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub added: usize,
+    /// modified desktop files, and those whose `TryExec` program was installed or
+    /// removed or whose icon was found
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    /// reading the directories and looking for modified files
+    pub walk_duration: Duration,
+    /// parsing the new and modified files
+    pub parse_duration: Duration,
+    /// the changes to the listed applications
+    pub diff: ApplicationsDiff,
+}
+
+/// Payload of `applications-changed`, applications are matched by `id`.
+#[derive(Debug, Default, Serialize, TS)]
+#[ts(export, export_to = "../../src/types/ApplicationsDiff.ts")]
+pub struct ApplicationsDiff {
+    pub added: Vec<Application>,
+    pub updated: Vec<Application>,
+    /// ids of the applications that are gone or hidden now
+    pub removed: Vec<String>,
+}
+
+/// Sent through the channel of the `stream_applications` command.
+#[derive(Debug, Serialize, TS)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/ScanEvent.ts")]
+pub enum ScanEvent {
+    /// the next listed applications, in list order
+    Batch { applications: Vec<Application> },
+    /// sent once the scan is done, durations are in milliseconds
+    Finished {
+        applications: usize,
+        added: usize,
+        updated: usize,
+        removed: usize,
+        unchanged: usize,
+        walk_ms: u32,
+        parse_ms: u32,
+        total_ms: u32,
+    },
+}
+
+impl ApplicationsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl ScanReport {
+    pub fn changed(&self) -> usize {
+        self.added + self.updated + self.removed
+    }
+}
+
+impl std::fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{changed} changed ({added} added, {updated} updated, {removed} removed), {unchanged} unchanged",
+            changed = self.changed(),
+            added = self.added,
+            updated = self.updated,
+            removed = self.removed,
+            unchanged = self.unchanged
+        )
+    }
+}
+
+fn index_file_name(format: CacheFormat) -> &'static str {
+    match format {
+        CacheFormat::Json => "apps_index.json",
+        CacheFormat::Binary => "apps_index.bin",
+    }
+}
+
+/// `$XDG_CACHE_HOME/cyberdeck/apps_index.json`, or `.bin` for the binary format
+pub fn get_index_path(format: CacheFormat) -> std::io::Result<PathBuf> {
+    paths::cache_dir()
+        .map(|dir| dir.join(index_file_name(format)))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Neither XDG_CACHE_HOME nor HOME is set",
+            )
+        })
+}
+
+// before the index, the applications were cached in this file
+fn remove_legacy_cache() {
+    let Some(legacy_dir) = paths::home_dir().map(|home| home.join(".local/share/cyberdeck")) else {
+        return;
+    };
+    let legacy_path = legacy_dir.join("apps_cache.json");
+    match fs::remove_file(&legacy_path) {
+        Ok(()) => {
+            info!("Removed the old application cache {legacy_path:?}");
+            // nothing else was kept there, the directory is only removed when empty
+            let _ = fs::remove_dir(&legacy_dir);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to remove the old application cache {legacy_path:?}: {e}"),
+    }
+}
+
+fn serialization_error(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(format!("Serialization error: {e}"))
+}
+
+impl AppIndex {
+    /// Reads the index, failing when it was built with another header than `expected`.
+    pub fn load(expected: &IndexHeader, format: CacheFormat) -> std::io::Result<Self> {
+        let start = Instant::now();
+        let index = match format {
+            CacheFormat::Json => Self::load_json(expected)?,
+            CacheFormat::Binary => Self::load_binary(expected)?,
+        };
+        info!(
+            "Loaded {count} indexed desktop files in {elapsed:?}",
+            count = index.files.len(),
+            elapsed = start.elapsed()
+        );
+        Ok(index)
+    }
+
+    fn load_json(expected: &IndexHeader) -> std::io::Result<Self> {
+        Self::decode_json(&fs::read(get_index_path(CacheFormat::Json)?)?, expected)
+    }
+
+    fn load_binary(expected: &IndexHeader) -> std::io::Result<Self> {
+        Self::decode_binary(&fs::read(get_index_path(CacheFormat::Binary)?)?, expected)
+    }
+
+    fn decode_json(data: &[u8], expected: &IndexHeader) -> std::io::Result<Self> {
+        let header_length = data
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or_else(|| std::io::Error::other("Invalid index, no header line"))?;
+        let (header_line, body) = data.split_at(header_length);
+        let header = decode_json_header(header_line)?;
+        if let Some(mismatch) = header.mismatch(expected) {
+            return Err(std::io::Error::other(format!("Outdated index, {mismatch}")));
+        }
+        let index: Self = serde_json::from_slice(body)
+            .map_err(|e| std::io::Error::other(format!("Deserialization error: {e}")))?;
+        Ok(Self { header, ..index })
+    }
+
+    fn decode_binary(data: &[u8], expected: &IndexHeader) -> std::io::Result<Self> {
+        let config = bincode::config::standard();
+        let (header, header_length): (IndexHeader, _) =
+            bincode::serde::decode_from_slice(data, config)
+                .map_err(|e| std::io::Error::other(format!("Invalid index header: {e}")))?;
+        if let Some(mismatch) = header.mismatch(expected) {
+            return Err(std::io::Error::other(format!("Outdated index, {mismatch}")));
+        }
+        let (index, _): (Self, _) =
+            bincode::serde::decode_from_slice(&data[header_length..], config)
+                .map_err(|e| std::io::Error::other(format!("Deserialization error: {e}")))?;
+        Ok(Self { header, ..index })
+    }
+
+    /// Writes a temporary file and renames it over the index, so a crash
+    /// can't leave a truncated index behind.
+    pub fn save(&self, format: CacheFormat) -> std::io::Result<()> {
+        let index_path = get_index_path(format)?;
+        if let Some(parent) = index_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        config_file::write_atomically(&index_path, self.encode(format)?)?;
+
+        // only the configured format is read, an index in the other one would be
+        // outdated when switching back
+        let other_format = match format {
+            CacheFormat::Json => CacheFormat::Binary,
+            CacheFormat::Binary => CacheFormat::Json,
+        };
+        match fs::remove_file(get_index_path(other_format)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    // the header followed by the index, see `decode_json` and `decode_binary`
+    fn encode(&self, format: CacheFormat) -> std::io::Result<Vec<u8>> {
+        match format {
+            CacheFormat::Json => {
+                let mut data = serde_json::to_vec(&self.header).map_err(serialization_error)?;
+                data.push(b'\n');
+                serde_json::to_writer(&mut data, self).map_err(serialization_error)?;
+                Ok(data)
+            }
+            CacheFormat::Binary => {
+                let config = bincode::config::standard();
+                let mut data = bincode::serde::encode_to_vec(&self.header, config)
+                    .map_err(serialization_error)?;
+                data.extend(
+                    bincode::serde::encode_to_vec(self, config).map_err(serialization_error)?,
+                );
+                Ok(data)
+            }
+        }
+    }
+
+    /// Copies the launches of the indexed applications from `history`.
+    pub fn update_launches(&mut self, history: &LaunchHistory) {
+        self.launches = self
+            .files
+            .values()
+            .filter_map(|file| file.application.as_ref())
+            .filter_map(|application| {
+                let launches = history.launches(&application.id)?;
+                Some((application.id.clone(), launches))
+            })
+            .collect();
+    }
+
+    /// Stores the launches of an application right after `LaunchHistory::record`.
+    pub fn record_launch(&mut self, id: &str, launches: Launches) {
+        self.launches.insert(id.to_string(), launches);
+    }
+
+    /// Brings the index up to date with `lookup_dirs`, calling `parse` with the path
+    /// and desktop file id only for new and modified desktop files and those whose
+    /// icon wasn't found, in parallel on the rayon pool. Everything is parsed again
+    /// when `header` differs from the one of the last scan.
+    ///
+    /// `on_batch` gets the listed applications in the order of `applications`,
+    /// up to `SCAN_BATCH_SIZE` desktop files at a time, as soon as they are parsed.
+    pub fn rescan(
+        &mut self,
+        lookup_dirs: &[String],
+        header: IndexHeader,
+        parse: impl Fn(&Path, &str) -> Option<Application> + Sync,
+        mut on_batch: impl FnMut(Vec<Application>),
+    ) -> ScanReport {
+        let mut report = ScanReport::default();
+        let mismatch = self.header.mismatch(&header);
+        let reparse_all = mismatch.is_some();
+        if let Some(mismatch) = mismatch {
+            if !self.files.is_empty() {
+                info!("Parsing all desktop files again, {mismatch}");
+            }
+            self.header = header;
+        }
+
+        let walk_start = Instant::now();
+        let (found_files, seen_directories) = self.walk(lookup_dirs);
+        // only one desktop file with an id is listed, like the spec asks
+        let desktop_files: Vec<FoundFile> = found_files
+            .into_iter()
+            .filter(|file| file.shadowed_by.is_none())
+            .collect();
+        report.walk_duration = walk_start.elapsed();
+
+        let parse_start = Instant::now();
+        let mut seen_files = HashSet::new();
+        for batch in desktop_files.chunks(SCAN_BATCH_SIZE) {
+            let states: Vec<FileState> = batch
+                .iter()
+                .map(|found| match self.files.get(&found.path) {
+                    Some(file)
+                        if !reparse_all
+                            && file.modified == found.modified
+                            && file.size == found.size =>
+                    {
+                        if file
+                            .application
+                            .as_ref()
+                            .is_some_and(Application::has_placeholder_icon)
+                        {
+                            FileState::IconMissing
+                        } else {
+                            FileState::Unchanged
+                        }
+                    }
+                    _ => FileState::Changed,
+                })
+                .collect();
+            // `collect` keeps the order of the files, however the parsing is scheduled
+            let mut parsed = batch
+                .par_iter()
+                .zip(&states)
+                .filter(|(_, state)| **state != FileState::Unchanged)
+                .map(|(found, _)| {
+                    #[cfg(debug_assertions)]
+                    debug!("Parsing desktop file {:?}", found.path);
+                    parse(&found.path, &found.id)
+                })
+                .collect::<Vec<_>>()
+                .into_iter();
+
+            let mut listed = vec![];
+            for (found, state) in batch.iter().zip(states) {
+                seen_files.insert(found.path.clone());
+                let reparsed = match state {
+                    FileState::Unchanged => None,
+                    FileState::IconMissing => parsed.next().filter(|application| {
+                        // the icon still isn't found
+                        self.files
+                            .get(&found.path)
+                            .is_none_or(|file| file.application != *application)
+                    }),
+                    FileState::Changed => parsed.next(),
+                };
+                let Some(application) = reparsed else {
+                    let Some(file) = self.files.get_mut(&found.path) else {
+                        continue;
+                    };
+                    // the `TryExec` program may have been installed or removed since
+                    let is_listed = file
+                        .application
+                        .as_ref()
+                        .is_some_and(Application::is_installed);
+                    if is_listed == file.listed {
+                        report.unchanged += 1;
+                    } else {
+                        report.updated += 1;
+                        file.listed = is_listed;
+                        match &file.application {
+                            Some(application) if is_listed => {
+                                report.diff.added.push(application.clone())
+                            }
+                            Some(application) => report.diff.removed.push(application.id.clone()),
+                            None => {}
+                        }
+                    }
+                    listed.extend(file.listed_application().cloned());
+                    continue;
+                };
+                let file = IndexedFile {
+                    modified: found.modified,
+                    size: found.size,
+                    listed: application.as_ref().is_some_and(Application::is_installed),
+                    application,
+                };
+                let application = file.listed_application().cloned();
+                listed.extend(application.clone());
+                let previous = self.files.insert(found.path.clone(), file);
+                match previous {
+                    Some(_) => report.updated += 1,
+                    None => report.added += 1,
+                }
+                let previous = previous.as_ref().and_then(IndexedFile::listed_application);
+                match (previous, application) {
+                    (None, Some(application)) => report.diff.added.push(application),
+                    (Some(previous), Some(application)) if *previous != application => {
+                        report.diff.updated.push(application)
+                    }
+                    (Some(previous), None) => report.diff.removed.push(previous.id.clone()),
+                    _ => {}
+                }
+            }
+            if !listed.is_empty() {
+                on_batch(listed);
+            }
+        }
+        report.parse_duration = parse_start.elapsed();
+
+        self.files.retain(|path, file| {
+            if seen_files.contains(path) {
+                return true;
+            }
+            report.removed += 1;
+            if let Some(application) = file.listed_application() {
+                report.diff.removed.push(application.id.clone());
+            }
+            false
+        });
+        self.directories
+            .retain(|path, _| seen_directories.contains(path));
+        report
+    }
+
+    // the desktop files below `lookup_dirs` in path order like the index,
+    // and the directories that were looked at
+    fn walk(&mut self, lookup_dirs: &[String]) -> (Vec<FoundFile>, HashSet<PathBuf>) {
+        let mut desktop_files = vec![];
+        let mut seen_directories = HashSet::new();
+        // desktop file id -> precedence of the lookup directory and the file used for the id
+        let mut used_files: HashMap<String, ((bool, usize), PathBuf)> = HashMap::new();
+        let data_home = paths::data_home();
+        for (position, lookup_dir) in lookup_dirs.iter().enumerate() {
+            let lookup_dir = Path::new(lookup_dir);
+            // like `$XDG_DATA_HOME` comes before `$XDG_DATA_DIRS`, the directories of
+            // the user override the system ones, otherwise the earlier directory wins
+            let is_system_dir = !data_home
+                .as_ref()
+                .is_some_and(|data_home| lookup_dir.starts_with(data_home));
+            let precedence = (is_system_dir, position);
+            // (path, is a directory), popped in path order
+            let mut pending = vec![(lookup_dir.to_path_buf(), true)];
+            while let Some((path, is_directory)) = pending.pop() {
+                if !is_directory {
+                    let Ok(metadata) = fs::metadata(&path) else {
+                        // e.g. a dangling symlink, dropped from the index
+                        continue;
+                    };
+                    // found by walking `lookup_dir`, so always below it
+                    let Some(id) = desktop_file_id(lookup_dir, &path) else {
+                        continue;
+                    };
+                    match used_files.entry(id.clone()) {
+                        Entry::Occupied(mut used) if precedence < used.get().0 => {
+                            used.insert((precedence, path.clone()));
+                        }
+                        Entry::Occupied(_) => {}
+                        Entry::Vacant(entry) => {
+                            entry.insert((precedence, path.clone()));
+                        }
+                    }
+                    desktop_files.push(FoundFile {
+                        modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                        size: metadata.len(),
+                        path,
+                        id,
+                        shadowed_by: None,
+                    });
+                    continue;
+                }
+                if !seen_directories.insert(path.clone()) {
+                    continue;
+                }
+                let Some((files, subdirectories)) = self.read_directory(&path) else {
+                    continue;
+                };
+                let mut entries: Vec<(PathBuf, bool)> = files
+                    .into_iter()
+                    .map(|file| (file, false))
+                    .chain(subdirectories.into_iter().map(|dir| (dir, true)))
+                    .collect();
+                entries.sort();
+                pending.extend(entries.into_iter().rev());
+            }
+        }
+
+        for file in &mut desktop_files {
+            file.shadowed_by = used_files
+                .get(&file.id)
+                .map(|(_, used)| used.clone())
+                .filter(|used| *used != file.path);
+        }
+        (desktop_files, seen_directories)
+    }
+
+    // desktop files and subdirectories of `directory`, from the index when it
+    // wasn't modified since the last scan
+    fn read_directory(&mut self, directory: &Path) -> Option<(Vec<PathBuf>, Vec<PathBuf>)> {
+        // lookup directories that don't exist, e.g. without flatpak, are skipped
+        let modified = fs::metadata(directory)
+            .and_then(|metadata| metadata.modified())
+            .ok()?;
+        if let Some(indexed) = self.directories.get(directory) {
+            if indexed.modified == modified {
+                return Some((
+                    indexed.desktop_files.clone(),
+                    indexed.subdirectories.clone(),
+                ));
+            }
+        }
+
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Failed to read directory {directory:?}: {e}");
+                return None;
+            }
+        };
+        let mut desktop_files = vec![];
+        let mut subdirectories = vec![];
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            // symlinked directories aren't followed, symlinked desktop files are
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                subdirectories.push(path);
+            } else if path.to_string_lossy().ends_with(".desktop") {
+                desktop_files.push(path);
+            }
+        }
+        desktop_files.sort();
+        subdirectories.sort();
+
+        self.directories.insert(
+            directory.to_path_buf(),
+            IndexedDirectory {
+                modified,
+                desktop_files: desktop_files.clone(),
+                subdirectories: subdirectories.clone(),
+            },
+        );
+        Some((desktop_files, subdirectories))
+    }
+
+    /// Whether nothing was indexed yet, e.g. before the first scan.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Every parsed application, listed or not.
+    pub fn indexed_applications(&self) -> impl Iterator<Item = &Application> {
+        self.files
+            .values()
+            .filter_map(|file| file.application.as_ref())
+    }
+
+    /// The indexed applications, grouped by lookup directory in the given order,
+    /// with their frecency.
+    pub fn applications(&self, lookup_dirs: &[String]) -> Vec<Application> {
+        let now = history::now();
+        let mut applications = vec![];
+        for lookup_dir in lookup_dirs {
+            let lookup_dir = Path::new(lookup_dir);
+            // paths are ordered by component, so a directory's files follow it
+            let files = self
+                .files
+                .range::<Path, _>((Bound::Included(lookup_dir), Bound::Unbounded))
+                .take_while(|(path, _)| path.starts_with(lookup_dir));
+            applications.extend(files.filter_map(|(_, file)| {
+                let mut application = file.listed_application()?.clone();
+                application.frecency = self
+                    .launches
+                    .get(&application.id)
+                    .map_or(0.0, |launches| launches.frecency(now));
+                Some(application)
+            }));
+        }
+        applications
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn application(number: usize) -> Application {
+        Application {
+            id: format!("/usr/share/applications/app{number}.desktop"),
+            name: format!("Application {number}"),
+            comment: String::from("Does something useful with files and folders"),
+            icon: format!("app{number}"),
+            icon_path: format!("/home/user/.cache/cyberdeck/icons/{number:016x}-96.png"),
+            exec: format!("/usr/bin/app{number} --new-window"),
+            categories: vec![String::from("Utility"), String::from("GTK")],
+            terminal: false,
+            try_exec: None,
+            search_key: format!("application {number}"),
+            frecency: 0.0,
+        }
+    }
+
+    // a test desktop file holds the name of its application, an empty one isn't listed
+    fn parse(path: &Path, _id: &str) -> Option<Application> {
+        let name = fs::read_to_string(path).ok()?;
+        (!name.is_empty()).then(|| Application {
+            id: path.to_string_lossy().into_owned(),
+            name,
+            ..application(0)
+        })
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cyberdeck-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // directories are only read again when their modification time changed,
+    // which doesn't always tick between quick changes
+    fn set_modified(path: &Path, seconds: u64) {
+        fs::File::open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    fn ids(applications: &[Application]) -> Vec<&str> {
+        applications.iter().map(|app| app.id.as_str()).collect()
+    }
+
+    #[test]
+    fn rescan_reports_what_changed() {
+        let dir = test_dir("rescan");
+        let lookup_dirs = [dir.to_string_lossy().into_owned()];
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let mut index = AppIndex::default();
+        let rescan = |index: &mut AppIndex| {
+            let mut batches = vec![];
+            let report = index.rescan(&lookup_dirs, IndexHeader::default(), parse, |batch| {
+                batches.extend(batch)
+            });
+            (report, batches)
+        };
+
+        fs::write(dir.join("a.desktop"), "A").unwrap();
+        fs::write(dir.join("b.desktop"), "B").unwrap();
+        fs::write(dir.join("hidden.desktop"), "").unwrap();
+        fs::write(dir.join("notes.txt"), "not a desktop file").unwrap();
+        set_modified(&dir, 1);
+        let (report, batches) = rescan(&mut index);
+        assert_eq!((report.added, report.updated, report.removed), (3, 0, 0));
+        assert_eq!(
+            ids(&report.diff.added),
+            [path("a.desktop"), path("b.desktop")]
+        );
+        assert_eq!(ids(&batches), [path("a.desktop"), path("b.desktop")]);
+
+        let (report, batches) = rescan(&mut index);
+        assert_eq!(report.unchanged, 3);
+        assert!(report.diff.is_empty());
+        assert_eq!(batches.len(), 2);
+
+        fs::write(dir.join("a.desktop"), "A renamed").unwrap();
+        fs::remove_file(dir.join("b.desktop")).unwrap();
+        fs::write(dir.join("c.desktop"), "C").unwrap();
+        fs::write(dir.join("hidden.desktop"), "Shown now").unwrap();
+        set_modified(&dir, 2);
+        let (report, _) = rescan(&mut index);
+        assert_eq!((report.added, report.updated, report.removed), (1, 2, 1));
+        assert_eq!(
+            ids(&report.diff.added),
+            [path("c.desktop"), path("hidden.desktop")]
+        );
+        assert_eq!(ids(&report.diff.updated), [path("a.desktop")]);
+        assert_eq!(report.diff.removed, [path("b.desktop")]);
+
+        fs::write(dir.join("c.desktop"), "").unwrap();
+        let (report, _) = rescan(&mut index);
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.diff.removed, [path("c.desktop")]);
+        assert_eq!(
+            ids(&index.applications(&lookup_dirs)),
+            [path("a.desktop"), path("hidden.desktop")]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_icons_are_looked_up_again() {
+        let dir = test_dir("missing-icons");
+        let lookup_dirs = [dir.to_string_lossy().into_owned()];
+        let icon_path = dir.join("app.png");
+        // the icon is found once its file exists
+        let parse = |path: &Path, id: &str| {
+            let icon = if icon_path.is_file() {
+                String::from("app")
+            } else {
+                String::from("placeholder:app.desktop")
+            };
+            Some(Application {
+                icon,
+                ..parse(path, id)?
+            })
+        };
+        let mut index = AppIndex::default();
+        let mut rescan = || index.rescan(&lookup_dirs, IndexHeader::default(), parse, |_| {});
+
+        fs::write(dir.join("app.desktop"), "App").unwrap();
+        assert_eq!(rescan().added, 1);
+        let report = rescan();
+        assert_eq!((report.unchanged, report.updated), (1, 0));
+
+        fs::write(&icon_path, "").unwrap();
+        let report = rescan();
+        assert_eq!((report.unchanged, report.updated), (0, 1));
+        assert_eq!(report.diff.updated[0].icon, "app");
+        assert_eq!(rescan().unchanged, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn frecency_is_read_from_the_index() {
+        let dir = test_dir("frecency");
+        let lookup_dirs = [dir.to_string_lossy().into_owned()];
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        fs::write(dir.join("a.desktop"), "A").unwrap();
+        fs::write(dir.join("b.desktop"), "B").unwrap();
+        let mut index = AppIndex::default();
+        index.rescan(&lookup_dirs, IndexHeader::default(), parse, |_| {});
+
+        let mut history = LaunchHistory::default();
+        history.record(&path("a.desktop"), history::now());
+        index.update_launches(&history);
+        let launches = history.record(&path("b.desktop"), history::now());
+        index.record_launch(&path("b.desktop"), launches);
+        history.record(&path("b.desktop"), history::now());
+
+        let frecency: Vec<f32> = index
+            .applications(&lookup_dirs)
+            .iter()
+            .map(|app| app.frecency)
+            .collect();
+        // the launch the index wasn't told about doesn't count
+        assert_eq!(frecency, [100.0, 100.0]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn desktop_file_id_joins_subdirectories_with_dashes() {
+        let lookup_dir = Path::new("/usr/share/applications");
+        assert_eq!(
+            desktop_file_id(
+                lookup_dir,
+                Path::new("/usr/share/applications/firefox.desktop")
+            ),
+            Some(String::from("firefox.desktop"))
+        );
+        assert_eq!(
+            desktop_file_id(
+                lookup_dir,
+                Path::new("/usr/share/applications/kde/konsole.desktop")
+            ),
+            Some(String::from("kde-konsole.desktop"))
+        );
+        assert_eq!(
+            desktop_file_id(lookup_dir, Path::new("/opt/apps/firefox.desktop")),
+            None
+        );
+    }
+
+    #[test]
+    fn earlier_lookup_dirs_shadow_files_with_the_same_id() {
+        let dir = test_dir("shadowing");
+        for file in [
+            "first/kde/konsole.desktop",
+            "first/firefox.desktop",
+            "second/kde-konsole.desktop",
+            "second/firefox.desktop",
+            "second/vlc.desktop",
+        ] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let lookup_dirs =
+            ["first", "second"].map(|name| dir.join(name).to_string_lossy().into_owned());
+
+        let found = find_desktop_files(&lookup_dirs);
+        let found: Vec<_> = found
+            .iter()
+            .map(|(path, id, shadowed_by)| (path.clone(), id.as_str(), shadowed_by.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (dir.join("first/firefox.desktop"), "firefox.desktop", None),
+                (
+                    dir.join("first/kde/konsole.desktop"),
+                    "kde-konsole.desktop",
+                    None
+                ),
+                (
+                    dir.join("second/firefox.desktop"),
+                    "firefox.desktop",
+                    Some(dir.join("first/firefox.desktop"))
+                ),
+                (
+                    dir.join("second/kde-konsole.desktop"),
+                    "kde-konsole.desktop",
+                    Some(dir.join("first/kde/konsole.desktop"))
+                ),
+                (dir.join("second/vlc.desktop"), "vlc.desktop", None),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // cargo test --release index_load_benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn index_load_benchmark() {
+        const APPLICATIONS: usize = 2000;
+        const RUNS: u32 = 20;
+
+        let header = IndexHeader::new(&CyberdeckConfig::default());
+        let mut index = AppIndex {
+            header: header.clone(),
+            ..AppIndex::default()
+        };
+        for number in 0..APPLICATIONS {
+            let application = application(number);
+            index.files.insert(
+                PathBuf::from(&application.id),
+                IndexedFile {
+                    modified: SystemTime::now(),
+                    size: 4096,
+                    application: Some(application),
+                    listed: true,
+                },
+            );
+        }
+
+        for format in [CacheFormat::Json, CacheFormat::Binary] {
+            let data = index.encode(format).unwrap();
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                let decoded = match format {
+                    CacheFormat::Json => AppIndex::decode_json(&data, &header),
+                    CacheFormat::Binary => AppIndex::decode_binary(&data, &header),
+                };
+                assert_eq!(decoded.unwrap().files.len(), APPLICATIONS);
+            }
+            println!(
+                "{format:?}: {size} KiB, {elapsed:?} per load of {APPLICATIONS} applications",
+                size = data.len() / 1024,
+                elapsed = start.elapsed() / RUNS
+            );
+        }
+    }
+}
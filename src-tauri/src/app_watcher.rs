@@ -0,0 +1,86 @@
+use crate::app_index::ApplicationsDiff;
+use crate::commands;
+use crate::config::APP_CONFIG;
+#[cfg(debug_assertions)]
+use log::debug;
+use log::{error, info};
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use once_cell::sync::Lazy;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::Emitter;
+
+// replaced whenever the lookup directories in the config change
+static APPS_WATCHER: Lazy<Mutex<Option<Debouncer<RecommendedWatcher>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+// package managers write many files in a row, they are picked up in one rescan
+const RESCAN_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Rescans the lookup directories whenever something in them changes and
+/// emits `applications-changed` with an `ApplicationsDiff` when the list changed.
+pub fn watch_applications(app_handle: tauri::AppHandle) {
+    let Ok(mut watcher_guard) = APPS_WATCHER.lock() else {
+        error!("Failed to lock applications watcher");
+        return;
+    };
+    *watcher_guard = None;
+
+    let lookup_dirs = match APP_CONFIG.lock() {
+        Ok(config_guard) => config_guard.lookup_dirs.resolve(),
+        Err(e) => {
+            error!("Failed to lock config: {e}");
+            return;
+        }
+    };
+
+    let on_change = move |result: DebounceEventResult| {
+        if let Err(e) = result {
+            error!("Failed to watch application directories: {e}");
+            return;
+        }
+        #[cfg(debug_assertions)]
+        debug!("Application directories changed, rescanning");
+        match commands::scan_applications(|_| {}) {
+            Ok((_, report)) => emit_applications_changed(&app_handle, &report.diff),
+            Err(e) => error!("Failed to rescan applications: {e}"),
+        }
+    };
+
+    let mut debouncer = match new_debouncer(RESCAN_DEBOUNCE, on_change) {
+        Ok(debouncer) => debouncer,
+        Err(e) => {
+            error!("Failed to create applications watcher: {e}");
+            return;
+        }
+    };
+    for lookup_dir in &lookup_dirs {
+        // e.g. the flatpak directory on systems without flatpak
+        if !Path::new(lookup_dir).is_dir() {
+            #[cfg(debug_assertions)]
+            debug!("Not watching missing application directory {lookup_dir}");
+            continue;
+        }
+        if let Err(e) = debouncer
+            .watcher()
+            .watch(Path::new(lookup_dir), RecursiveMode::Recursive)
+        {
+            error!("Failed to watch application directory {lookup_dir}: {e}");
+            continue;
+        }
+        info!("Watching application directory {lookup_dir}");
+    }
+    *watcher_guard = Some(debouncer);
+}
+
+/// Emits `applications-changed`, unless nothing changed.
+pub fn emit_applications_changed(app_handle: &tauri::AppHandle, diff: &ApplicationsDiff) {
+    if diff.is_empty() {
+        return;
+    }
+    if let Err(e) = app_handle.emit("applications-changed", diff) {
+        error!("Failed to notify the frontend about changed applications: {e}");
+    }
+}
@@ -0,0 +1,387 @@
+use crate::app_index::{self, AppIndex, APP_INDEX};
+use crate::commands::{self, Application};
+use crate::config::{self, ConfigOptions, APP_CONFIG};
+use crate::doctor;
+use crate::icon_cache;
+use crate::icons;
+use crate::paths::{self, PathOverrides};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "cyberdeck",
+    version,
+    about = "An application launcher with the power of web styling"
+)]
+pub struct Cli {
+    /// Load `profiles/<PROFILE>.toml` (or `.jsonc`, `.json`) on top of the user config
+    #[arg(long, env = "CYBERDECK_PROFILE", global = true)]
+    pub profile: Option<String>,
+
+    /// Override a config value, e.g. `--set ui.apps_per_page=8`.
+    /// Nested keys are separated by dots: `--set lookup_dirs.extend=/opt/apps`
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub overrides: Vec<String>,
+
+    /// Use `themes/<THEME>/` from the config directory for this run,
+    /// same as `--set ui.theme=<THEME>`
+    #[arg(long, global = true)]
+    pub theme: Option<String>,
+
+    /// Config file, profiles and themes [default: $XDG_CONFIG_HOME/cyberdeck]
+    #[arg(
+        long,
+        env = "CYBERDECK_CONFIG_DIR",
+        value_name = "DIR",
+        global = true,
+        help_heading = "Directories"
+    )]
+    pub config_dir: Option<PathBuf>,
+
+    /// Application index and icon cache [default: $XDG_CACHE_HOME/cyberdeck]
+    #[arg(
+        long,
+        env = "CYBERDECK_CACHE_DIR",
+        value_name = "DIR",
+        global = true,
+        help_heading = "Directories"
+    )]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Cyberdeck data of the user [default: $XDG_DATA_HOME/cyberdeck]
+    #[arg(
+        long,
+        env = "CYBERDECK_DATA_DIR",
+        value_name = "DIR",
+        global = true,
+        help_heading = "Directories"
+    )]
+    pub data_dir: Option<PathBuf>,
+
+    /// Launch history [default: $XDG_STATE_HOME/cyberdeck]
+    #[arg(
+        long,
+        env = "CYBERDECK_STATE_DIR",
+        value_name = "DIR",
+        global = true,
+        help_heading = "Directories"
+    )]
+    pub state_dir: Option<PathBuf>,
+
+    /// Runtime files [default: $XDG_RUNTIME_DIR/cyberdeck]
+    #[arg(
+        long,
+        env = "CYBERDECK_RUNTIME_DIR",
+        value_name = "DIR",
+        global = true,
+        help_heading = "Directories"
+    )]
+    pub runtime_dir: Option<PathBuf>,
+
+    /// Used instead of $XDG_DATA_HOME to find icons and to tell the desktop files
+    /// of the user from the system ones
+    #[arg(
+        long,
+        env = "CYBERDECK_DATA_HOME",
+        value_name = "DIR",
+        global = true,
+        help_heading = "Directories"
+    )]
+    pub data_home: Option<PathBuf>,
+
+    /// Runs without opening the launcher window
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Scan the lookup directories like the launcher and print the applications,
+    /// without updating the cache
+    List {
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+        /// Only applications in this category, e.g. `Development`
+        #[arg(long)]
+        category: Option<String>,
+        /// Only graphical or only terminal (`Terminal=true`) applications
+        #[arg(long, value_enum)]
+        mode: Option<AppMode>,
+        /// Only applications whose name, generic name, keywords or command line
+        /// contain QUERY, like the launcher search
+        query: Option<String>,
+    },
+    /// Bring the application index and icon cache up to date,
+    /// e.g. from a systemd user timer or a pacman hook
+    RebuildCache {
+        /// Discard the index and the cached icons and build them from scratch
+        #[arg(long)]
+        full: bool,
+        /// Scale factor of the screen the launcher opens on
+        /// [default: the one the index was built with, or 1]
+        #[arg(long)]
+        scale: Option<i32>,
+    },
+    /// Explain which desktop files are listed or skipped and why,
+    /// which icons were found, and the effective config and directories
+    Doctor {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ListFormat {
+    /// Aligned columns
+    Table,
+    /// One JSON array
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// How an application runs.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AppMode {
+    Graphical,
+    /// `Terminal=true`, runs in the configured terminal
+    Terminal,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the JSON Schema of the config file, for editor completion and validation
+    Schema,
+    /// Print the directories Cyberdeck reads and writes
+    Paths,
+}
+
+impl Cli {
+    pub fn config_options(&self) -> ConfigOptions {
+        ConfigOptions {
+            profile: self.profile.clone(),
+            overrides: self
+                .overrides
+                .iter()
+                .cloned()
+                .chain(self.theme.iter().map(|theme| format!("ui.theme={theme}")))
+                .collect(),
+            create_user_config: self.command.is_none(),
+        }
+    }
+
+    pub fn path_overrides(&self) -> PathOverrides {
+        PathOverrides {
+            config_dir: self.config_dir.clone(),
+            cache_dir: self.cache_dir.clone(),
+            data_dir: self.data_dir.clone(),
+            state_dir: self.state_dir.clone(),
+            runtime_dir: self.runtime_dir.clone(),
+            data_home: self.data_home.clone(),
+        }
+    }
+}
+
+/// Runs a headless subcommand and returns the process exit code.
+pub fn run_command(command: Command) -> i32 {
+    match command {
+        Command::Config {
+            action: ConfigCommand::Schema,
+        } => {
+            println!("{}", config::config_json_schema());
+            0
+        }
+        Command::Config {
+            action: ConfigCommand::Paths,
+        } => {
+            for (name, dir) in paths::directories() {
+                match dir {
+                    Some(dir) => println!("{name:<8} {}", dir.display()),
+                    None => println!("{name:<8} (not available)"),
+                }
+            }
+            0
+        }
+        Command::List {
+            format,
+            category,
+            mode,
+            query,
+        } => list_applications(format, category, mode, query),
+        Command::RebuildCache { full, scale } => rebuild_cache(full, scale),
+        Command::Doctor { json } => print_diagnostics(json),
+    }
+}
+
+// the index is only valid for the scale factor it was built with, so unless
+// another one is given, the one of the last launcher window is kept
+fn set_scale_factor(scale: Option<i32>) {
+    let scale = scale.or_else(|| {
+        let format = APP_CONFIG.lock().ok()?.cache.format;
+        let header = app_index::read_header(format).ok()?;
+        i32::try_from(header.scale_factor).ok()
+    });
+    icons::set_scale_factor(scale.unwrap_or(1));
+}
+
+fn list_applications(
+    format: ListFormat,
+    category: Option<String>,
+    mode: Option<AppMode>,
+    query: Option<String>,
+) -> i32 {
+    set_scale_factor(None);
+    // only `rebuild-cache` writes the index and the icon cache
+    let applications = match commands::scan_applications_read_only() {
+        Ok((applications, _)) => applications,
+        Err(e) => {
+            eprintln!("Failed to scan applications: {e}");
+            return 1;
+        }
+    };
+
+    let query = query.map(|query| query.to_lowercase());
+    let applications: Vec<Application> = applications
+        .into_iter()
+        .filter(|app| {
+            category.as_ref().is_none_or(|category| {
+                app.categories
+                    .iter()
+                    .any(|app_category| app_category.eq_ignore_ascii_case(category))
+            })
+        })
+        .filter(|app| mode.is_none_or(|mode| app.terminal == (mode == AppMode::Terminal)))
+        .filter(|app| {
+            query
+                .as_ref()
+                .is_none_or(|query| app.search_key.contains(query.as_str()))
+        })
+        .collect();
+
+    match print_applications(&applications, format) {
+        Ok(()) => 0,
+        // e.g. piped into `head`
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => 0,
+        Err(e) => {
+            eprintln!("Failed to print applications: {e}");
+            1
+        }
+    }
+}
+
+fn print_applications(applications: &[Application], format: ListFormat) -> std::io::Result<()> {
+    let mut out = std::io::stdout().lock();
+    match format {
+        ListFormat::Table => {
+            let width = |column: &dyn Fn(&Application) -> String, header: &str| {
+                applications
+                    .iter()
+                    .map(|app| column(app).chars().count())
+                    .chain([header.len()])
+                    .max()
+                    .unwrap_or_default()
+            };
+            let categories = |app: &Application| app.categories.join(";");
+            let name_width = width(&|app| app.name.clone(), "NAME");
+            let categories_width = width(&categories, "CATEGORIES");
+            writeln!(
+                out,
+                "{:name_width$}  {:categories_width$}  EXEC",
+                "NAME", "CATEGORIES"
+            )?;
+            for app in applications {
+                writeln!(
+                    out,
+                    "{:name_width$}  {:categories_width$}  {}",
+                    app.name,
+                    categories(app),
+                    app.exec
+                )?;
+            }
+        }
+        ListFormat::Json => {
+            let json = serde_json::to_string_pretty(applications).map_err(std::io::Error::other)?;
+            writeln!(out, "{json}")?;
+        }
+        ListFormat::Ndjson => {
+            for app in applications {
+                let json = serde_json::to_string(app).map_err(std::io::Error::other)?;
+                writeln!(out, "{json}")?;
+            }
+        }
+    }
+    out.flush()
+}
+
+fn rebuild_cache(full: bool, scale: Option<i32>) -> i32 {
+    let start = Instant::now();
+    set_scale_factor(scale);
+    let (icon_size, icon_theme) = match APP_CONFIG.lock() {
+        Ok(config) => (config.ui.icon_size, config.icon_theme.clone()),
+        Err(e) => {
+            eprintln!("Failed to lock config: {e}");
+            return 1;
+        }
+    };
+
+    if full {
+        match APP_INDEX.lock() {
+            // every desktop file is parsed again and the index written anew
+            Ok(mut index) => *index = AppIndex::default(),
+            Err(e) => {
+                eprintln!("Failed to lock the application index: {e}");
+                return 1;
+            }
+        }
+        // keeps nothing, placeholders are generated again by the scan
+        icon_cache::collect_garbage(&HashSet::new());
+    }
+
+    let (applications, report) = match commands::scan_applications(|_| {}) {
+        Ok(scan) => scan,
+        Err(e) => {
+            eprintln!("Failed to scan applications: {e}");
+            return 1;
+        }
+    };
+    let cached_icons = icons::prewarm_icon_cache(&applications, icon_size, &icon_theme);
+    println!(
+        "Indexed {count} applications ({report}) and cached {cached_icons} icons in {elapsed:?}",
+        count = applications.len(),
+        elapsed = start.elapsed()
+    );
+    0
+}
+
+fn print_diagnostics(json: bool) -> i32 {
+    set_scale_factor(None);
+    let report = match doctor::diagnose() {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Failed to diagnose: {e}");
+            return 1;
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("Failed to serialize the report: {e}");
+                return 1;
+            }
+        }
+    } else {
+        print!("{report}");
+    }
+    0
+}
@@ -1,26 +1,64 @@
+use crate::app_index::{IndexHeader, ScanEvent, ScanReport, APP_INDEX};
+use crate::app_watcher;
 use crate::config;
+use crate::history::{self, LaunchHistory};
+use crate::icon_cache;
+use crate::icon_theme;
+use crate::icons;
+use crate::placeholder;
 use aho_corasick::AhoCorasick;
 use freedesktop_file_parser::{EntryType, LocaleString};
 #[cfg(debug_assertions)]
-use log::{debug, info};
-use log::{error, warn};
+use log::{debug, warn};
+use log::{error, info};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::env;
-use std::fs;
-use std::path::Path;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
 use ts_rs::TS;
-use walkdir::WalkDir;
 
-#[derive(Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/types/Application.ts")]
 pub struct Application {
+    /// path of the desktop file, identifies the application in `ApplicationsDiff`
+    pub id: String,
     pub name: String,
     pub comment: String,
+    /// `Icon` of the desktop entry, served through the `cyberdeck-icon` protocol.
+    /// A generated placeholder (`placeholder:<desktop file id>`) when the icon couldn't be found
     pub icon: String,
+    /// the file `icon` is served from, rasterised into the icon cache by the scan;
+    /// empty when the placeholder couldn't be written either
+    pub icon_path: String,
     pub exec: String,
-    // pub launches_count: u32,
+    /// `Categories` of the desktop entry, e.g. `["Development", "IDE"]`
+    pub categories: Vec<String>,
+    /// runs in `terminal_app`, `Terminal=true` in the desktop entry
+    pub terminal: bool,
+    /// `TryExec` of the desktop entry, only listed while this program is installed.
+    /// Checked on every scan, installing it doesn't touch the desktop file
+    pub try_exec: Option<String>,
+    /// what the search matches against: the lowercase name, generic name, keywords
+    /// and command line, one per line so a query can't match across them
+    pub search_key: String,
+    /// how often and recently it was launched, filled in from the index
+    pub frecency: f32,
+}
+
+impl Application {
+    /// Whether the `TryExec` program is installed, true without one.
+    pub fn is_installed(&self) -> bool {
+        self.try_exec.as_deref().is_none_or(is_program_installed)
+    }
+
+    /// Whether the icon wasn't found when the desktop file was parsed,
+    /// `icon` is a placeholder then, or empty when that couldn't be written.
+    pub fn has_placeholder_icon(&self) -> bool {
+        self.icon.is_empty() || self.icon.starts_with(placeholder::PLACEHOLDER_PREFIX)
+    }
 }
 
 impl std::fmt::Display for Application {
@@ -39,7 +77,24 @@ pub fn exit(app_handle: tauri::AppHandle) {
 }
 
 #[tauri::command]
-pub fn start_program(app_handle: tauri::AppHandle, exec: String) -> bool {
+pub async fn start_program(
+    app_handle: tauri::AppHandle,
+    exec: String,
+    in_terminal: Option<bool>,
+    app_id: String,
+) -> bool {
+    let exec = if in_terminal.unwrap_or(false) {
+        match config::APP_CONFIG.lock() {
+            Ok(config) => format!("{terminal_app} {exec}", terminal_app = config.terminal_app),
+            Err(e) => {
+                error!("Error while locking config: {e}");
+                return false;
+            }
+        }
+    } else {
+        exec
+    };
+
     // use nohup to detach the process and redirect output
     let shell_cmd = format!("nohup {exec} > /dev/null 2>&1 &");
 
@@ -47,7 +102,19 @@ pub fn start_program(app_handle: tauri::AppHandle, exec: String) -> bool {
         Ok(_) => {
             #[cfg(debug_assertions)]
             info!("Successfully started program: {exec}");
-            app_handle.exit(0);
+            // the history is written off the async runtime, but before exiting
+            let recorded =
+                tauri::async_runtime::spawn_blocking(move || history::record_launch(&app_id));
+            if let Err(e) = recorded.await {
+                error!("Failed to record the launch: {e}");
+            }
+            let keep_open = config::APP_CONFIG
+                .lock()
+                .map(|config| config.ui.keep_open_after_launch)
+                .unwrap_or(false);
+            if !keep_open {
+                app_handle.exit(0);
+            }
             true
         }
         Err(e) => {
@@ -59,92 +126,203 @@ pub fn start_program(app_handle: tauri::AppHandle, exec: String) -> bool {
 
 #[tauri::command]
 pub async fn get_desktop_applications() -> Vec<Application> {
-    let mut applications: Vec<Application> = vec![];
-
-    let config_guard = match config::APP_CONFIG.lock() {
-        Ok(conf) => conf.clone(),
+    // the scan blocks on the file system, it runs off the async runtime
+    let scan = tauri::async_runtime::spawn_blocking(|| scan_applications(|_| {}));
+    match scan.await {
+        Ok(Ok((applications, _))) => applications,
+        Ok(Err(e)) => {
+            error!("{e}");
+            vec![]
+        }
         Err(e) => {
-            error!("Error while locking config: {e}");
-            return applications;
+            error!("Application scan failed: {e}");
+            vec![]
         }
-    };
+    }
+}
 
-    let applications_paths = &config_guard.lookup_dirs;
+/// Returns the cached applications right away and rescans in the background,
+/// what changed is emitted as `applications-changed` once the rescan is done.
+/// `None` without cached applications, `stream_applications` scans then.
+#[tauri::command]
+pub async fn load_applications(app_handle: tauri::AppHandle) -> Option<Vec<Application>> {
+    let applications = try_get_cached_applications().await?;
+    tauri::async_runtime::spawn_blocking(move || match scan_applications(|_| {}) {
+        Ok((_, report)) => app_watcher::emit_applications_changed(&app_handle, &report.diff),
+        Err(e) => error!("Failed to rescan applications: {e}"),
+    });
+    Some(applications)
+}
+
+/// Scans like `get_desktop_applications`, but sends the applications through
+/// `on_event` in batches while the scan goes on, so the first page shows up early.
+#[tauri::command]
+pub async fn stream_applications(on_event: Channel<ScanEvent>) -> Result<(), String> {
+    let start = Instant::now();
+    let batch_channel = on_event.clone();
+    let (applications, report) = tauri::async_runtime::spawn_blocking(move || {
+        scan_applications(|applications| {
+            if let Err(e) = batch_channel.send(ScanEvent::Batch { applications }) {
+                error!("Failed to send scanned applications: {e}");
+            }
+        })
+    })
+    .await
+    .map_err(|e| format!("Application scan failed: {e}"))??;
+    let millis = |duration: Duration| u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+    on_event
+        .send(ScanEvent::Finished {
+            applications: applications.len(),
+            added: report.added,
+            updated: report.updated,
+            removed: report.removed,
+            unchanged: report.unchanged,
+            walk_ms: millis(report.walk_duration),
+            parse_ms: millis(report.parse_duration),
+            total_ms: millis(start.elapsed()),
+        })
+        .map_err(|e| format!("Failed to send the scan result: {e}"))
+}
+
+/// Brings the application index up to date and returns the listed applications
+/// along with what changed, `on_batch` is passed on to `AppIndex::rescan`.
+/// Blocks while desktop files are parsed on the rayon pool, async callers use
+/// `spawn_blocking`. Also run by the lookup directory watcher.
+pub fn scan_applications(
+    on_batch: impl FnMut(Vec<Application>),
+) -> Result<(Vec<Application>, ScanReport), String> {
+    rescan_index(on_batch, true)
+}
+
+/// Scans like `scan_applications`, but leaves the stored index and the icon
+/// cache alone, e.g. for `cyberdeck list`.
+pub fn scan_applications_read_only() -> Result<(Vec<Application>, ScanReport), String> {
+    rescan_index(|_| {}, false)
+}
+
+// `persist` writes placeholders, cached icons and the index
+fn rescan_index(
+    on_batch: impl FnMut(Vec<Application>),
+    persist: bool,
+) -> Result<(Vec<Application>, ScanReport), String> {
+    let config_guard = config::APP_CONFIG
+        .lock()
+        .map_err(|e| format!("Error while locking config: {e}"))?
+        .clone();
+
+    let applications_paths = config_guard.lookup_dirs.resolve();
     // get current desktop environment
     let desktop_environment = &config_guard.desktop_environment;
     // env::var("XDG_CURRENT_DESKTOP").unwrap_or_else(|_| String::from("Hyprland"));
     let terminal_app = &config_guard.terminal_app;
-    // for manually searching for some KDE icons, as the freedesktop_file_parser chooses
-    // the "hicolor" theme by default.
-    let kde_icon_theme = &config_guard.kde_icon_theme;
+    // detected from the desktop settings when empty
+    let icon_theme = &config_guard.icon_theme;
+    let icon_size = config_guard.ui.icon_size;
+    let scale = icons::scale_factor();
     #[cfg(debug_assertions)]
     {
         info!("Current desktop environment: {desktop_environment}");
         info!("Current default terminal: {terminal_app}");
-        info!("Current KDE icon theme: {kde_icon_theme}");
+        info!("Current icon theme: {icon_theme}");
     }
 
-    for applications_path in applications_paths {
-        for entry in WalkDir::new(applications_path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let file_path = entry.path().to_string_lossy();
-            if !file_path.ends_with(".desktop") {
-                #[cfg(debug_assertions)]
-                debug!("Skipping '{file_path}': not a desktop file");
-                continue;
-            }
-
+    let mut index = APP_INDEX
+        .lock()
+        .map_err(|e| format!("Error while locking the application index: {e}"))?;
+    // icons that weren't found may have been installed along with the changes
+    icons::forget_resolved_icons();
+    let report = index.rescan(
+        &applications_paths,
+        IndexHeader::new(&config_guard),
+        |file_path, desktop_file_id| {
             #[cfg(debug_assertions)]
-            debug!("Processing: {file_path}");
-
+            debug!("Processing: {file_path:?}");
             match parse_application_from_file(
-                file_path.to_string(),
+                file_path.to_string_lossy().into_owned(),
+                desktop_file_id,
                 desktop_environment,
                 terminal_app,
-                kde_icon_theme,
-            )
-            .await
-            {
-                Some(parsed_app) => {
+                icon_theme,
+                icon_size,
+            ) {
+                Ok((mut parsed_app, _)) => {
+                    if persist {
+                        prepare_application_icon(&mut parsed_app, u32::from(icon_size) * scale);
+                    }
                     #[cfg(debug_assertions)]
                     debug!("Adding application: {parsed_app}");
-                    applications.push(parsed_app);
+                    Some(parsed_app)
                 }
-                None => continue,
-            };
-        }
+                Err(SkipReason::ParseError { message }) => {
+                    error!("Error parsing desktop file {file_path:?}: {message}");
+                    None
+                }
+                Err(_reason) => {
+                    #[cfg(debug_assertions)]
+                    debug!("Skipping {file_path:?}: {_reason}");
+                    None
+                }
+            }
+        },
+        on_batch,
+    );
+    if report.changed() > 0 {
+        // e.g. the first scan, the launches of new applications are read once
+        index.update_launches(&LaunchHistory::load());
     }
-
-    #[cfg(debug_assertions)]
+    let applications = index.applications(&applications_paths);
     info!(
-        "Total applications found: {count}",
+        "Scanned applications in {duration:?} (walk {walk:?}, parse {parse:?} on {threads} threads): {report}, {count} listed",
+        duration = report.walk_duration + report.parse_duration,
+        walk = report.walk_duration,
+        parse = report.parse_duration,
+        threads = rayon::current_num_threads(),
         count = applications.len()
     );
-    match cache_apps(&applications).await {
-        Ok(()) => {
-            #[cfg(debug_assertions)]
-            info!("Applications cached successfully")
-        }
-        Err(e) => {
-            error!("Error occurred when writing cache to file: {e}")
+
+    icons::register_icons(&applications, u32::from(icon_size) * scale);
+    if persist && report.changed() > 0 {
+        // unlisted applications keep their icons, for when their `TryExec` program is installed
+        icons::clean_icon_cache(index.indexed_applications());
+        match index.save(config_guard.cache.format) {
+            Ok(()) => {
+                #[cfg(debug_assertions)]
+                info!("Application index saved successfully")
+            }
+            Err(e) => {
+                error!("Error occurred when writing the application index: {e}")
+            }
         }
     }
 
-    applications
+    Ok((applications, report))
 }
 
 #[tauri::command]
 pub async fn try_get_cached_applications() -> Option<Vec<Application>> {
-    match read_cached_apps().await {
-        Ok(apps) => {
+    let (lookup_dirs, icon_size) = match config::APP_CONFIG.lock() {
+        Ok(config) => (config.lookup_dirs.resolve(), config.ui.icon_size),
+        Err(e) => {
+            error!("Error while locking config: {e}");
+            return None;
+        }
+    };
+    match APP_INDEX.lock() {
+        Ok(index) if index.is_empty() => {
+            #[cfg(debug_assertions)]
+            info!("No cached applications yet");
+            None
+        }
+        Ok(index) => {
             #[cfg(debug_assertions)]
             info!("Successfully read cached applications");
+            let apps = index.applications(&lookup_dirs);
+            // the index was loaded for the current scale factor
+            icons::register_icons(&apps, u32::from(icon_size) * icons::scale_factor());
             Some(apps)
         }
         Err(e) => {
-            error!("Error while reading cached apps: {e}");
+            error!("Error while locking the application index: {e}");
             None
         }
     }
@@ -155,19 +333,110 @@ pub fn is_dev() -> bool {
     cfg!(debug_assertions)
 }
 
-async fn parse_application_from_file(
+/// Why a desktop file isn't listed.
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/SkipReason.ts")]
+pub enum SkipReason {
+    /// unreadable, without a `[Desktop Entry]` section or not valid
+    ParseError {
+        message: String,
+    },
+    /// `Type` isn't `Application`, e.g. a link
+    NotAnApplication,
+    NoExec,
+    /// `Hidden=true`, the entry counts as deleted
+    Hidden,
+    NoDisplay,
+    /// `OnlyShowIn` doesn't contain the configured desktop environment
+    OnlyShowIn {
+        desktop_environment: String,
+    },
+    /// `NotShowIn` contains the configured desktop environment
+    NotShowIn {
+        desktop_environment: String,
+    },
+    /// the `TryExec` program isn't installed
+    TryExec {
+        program: String,
+    },
+    /// a desktop file with the same id is used instead, from a directory of the user
+    /// or an earlier lookup directory
+    Shadowed {
+        by: PathBuf,
+    },
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::ParseError { message } => write!(f, "parse error: {message}"),
+            SkipReason::NotAnApplication => write!(f, "not an application entry"),
+            SkipReason::NoExec => write!(f, "no Exec"),
+            SkipReason::Hidden => write!(f, "Hidden=true"),
+            SkipReason::NoDisplay => write!(f, "NoDisplay=true"),
+            SkipReason::OnlyShowIn {
+                desktop_environment,
+            } => write!(f, "OnlyShowIn doesn't include {desktop_environment}"),
+            SkipReason::NotShowIn {
+                desktop_environment,
+            } => write!(f, "NotShowIn includes {desktop_environment}"),
+            SkipReason::TryExec { program } => write!(f, "TryExec program '{program}' not found"),
+            SkipReason::Shadowed { by } => write!(f, "shadowed by {by:?}"),
+        }
+    }
+}
+
+/// Which icon file was chosen for an application.
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/IconResolution.ts")]
+pub enum IconResolution {
+    Found {
+        icon: String,
+        path: PathBuf,
+    },
+    /// the desktop entry has no `Icon`, a placeholder is used
+    Missing {
+        placeholder: String,
+    },
+    /// `Icon` is neither in the icon theme, its fallbacks nor the pixmaps,
+    /// a placeholder is used
+    NotFound {
+        icon: String,
+        icon_theme: String,
+        placeholder: String,
+    },
+}
+
+impl std::fmt::Display for IconResolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IconResolution::Found { icon, path } => write!(f, "'{icon}' at {path:?}"),
+            IconResolution::Missing { .. } => write!(f, "no Icon, using a placeholder"),
+            IconResolution::NotFound {
+                icon, icon_theme, ..
+            } => write!(
+                f,
+                "'{icon}' not found in icon theme '{icon_theme}', using a placeholder"
+            ),
+        }
+    }
+}
+
+/// Parses a desktop file, `desktop_file_id` (see `app_index::desktop_file_id`)
+/// names the placeholder when the icon isn't found.
+pub fn parse_application_from_file(
     file_path: String,
+    desktop_file_id: &str,
     desktop_environment: &String,
     terminal_app: &String,
-    kde_icon_theme: &str,
-) -> Option<Application> {
-    let content = match std::fs::read_to_string(&file_path) {
-        Ok(content) => content,
-        Err(e) => {
-            error!("Error reading file {file_path}: {e}");
-            return None;
-        }
-    };
+    icon_theme: &str,
+    icon_size: u16,
+) -> Result<(Application, IconResolution), SkipReason> {
+    let content = std::fs::read_to_string(&file_path).map_err(|e| SkipReason::ParseError {
+        message: e.to_string(),
+    })?;
 
     // extract only the [Desktop Entry] section
     // upd: so far this is needed only for realvnc-vncviewer.desktop
@@ -179,142 +448,175 @@ async fn parse_application_from_file(
             format!("[Desktop Entry]{}", &section[..next_section])
         }
         None => {
-            #[cfg(debug_assertions)]
-            debug!("No [Desktop Entry] section found in {file_path}");
-            return None;
+            return Err(SkipReason::ParseError {
+                message: String::from("no [Desktop Entry] section"),
+            });
         }
     };
 
-    let desktop_file = match freedesktop_file_parser::parse(&desktop_entry_content) {
-        Ok(parsed) => parsed,
-        Err(e) => {
-            error!("Error parsing desktop file {file_path}: {e}");
-            return None;
+    let desktop_file = freedesktop_file_parser::parse(&desktop_entry_content).map_err(|e| {
+        SkipReason::ParseError {
+            message: e.to_string(),
         }
-    };
+    })?;
 
     let desktop_entry = desktop_file.entry;
 
     // skip if not an application entry
-    if let EntryType::Application(application) = &desktop_entry.entry_type {
-        // skip if no exec field
-        let app_exec = match application.exec.clone() {
-            Some(exec) => {
-                let cleaned = clean_exec_command(exec, &desktop_entry.name.default).await;
-                match application.terminal {
-                    Some(is_terminal) => {
-                        if is_terminal {
-                            format!("{terminal_app} {cleaned}")
-                        } else {
-                            cleaned
-                        }
-                    }
-                    None => cleaned,
-                }
-            }
-            None => {
-                #[cfg(debug_assertions)]
-                {
-                    debug!(
-                        "Skipping {app_name}: No exec field",
-                        app_name = desktop_entry.name.default
-                    );
-                }
-                return None;
-            }
-        };
+    let EntryType::Application(application) = &desktop_entry.entry_type else {
+        return Err(SkipReason::NotAnApplication);
+    };
 
-        // skip if application is hidden or shouldn't be displayed
-        if desktop_entry.hidden.unwrap_or(false) || desktop_entry.no_display.unwrap_or(false) {
-            #[cfg(debug_assertions)]
-            {
-                debug!(
-                    "Skipping {app_name}: Hidden or no display",
-                    app_name = desktop_entry.name.default
-                );
-            }
-            return None;
-        }
+    // skip if no exec field
+    let Some(exec) = application.exec.clone() else {
+        return Err(SkipReason::NoExec);
+    };
+    let cleaned = clean_exec_command(exec, &desktop_entry.name.default);
+    let search_key = [desktop_entry.name.default.as_str()]
+        .into_iter()
+        .chain(
+            desktop_entry
+                .generic_name
+                .as_ref()
+                .map(|generic_name| generic_name.default.as_str()),
+        )
+        .chain(
+            application
+                .keywords
+                .iter()
+                .flat_map(|keywords| keywords.default.iter().map(String::as_str)),
+        )
+        .chain([cleaned.as_str()])
+        .collect::<Vec<_>>()
+        .join("\n")
+        .to_lowercase();
+    let app_exec = if application.terminal.unwrap_or(false) {
+        format!("{terminal_app} {cleaned}")
+    } else {
+        cleaned
+    };
 
-        // check if application should be shown in current desktop environment
-        let only_show_in = desktop_entry.only_show_in.unwrap_or_default();
-        let not_show_in = desktop_entry.not_show_in.unwrap_or_default();
+    // skip if application is hidden or shouldn't be displayed
+    if desktop_entry.hidden.unwrap_or(false) {
+        return Err(SkipReason::Hidden);
+    }
+    if desktop_entry.no_display.unwrap_or(false) {
+        return Err(SkipReason::NoDisplay);
+    }
 
-        if !only_show_in.is_empty() && !only_show_in.contains(desktop_environment) {
-            #[cfg(debug_assertions)]
-            {
-                debug!(
-                    "Skipping {app_name}: Not compatible with current desktop environment",
-                    app_name = desktop_entry.name.default
-                );
-            }
-            return None;
-        }
+    // check if application should be shown in current desktop environment
+    let only_show_in = desktop_entry.only_show_in.unwrap_or_default();
+    let not_show_in = desktop_entry.not_show_in.unwrap_or_default();
+
+    if !only_show_in.is_empty() && !only_show_in.contains(desktop_environment) {
+        return Err(SkipReason::OnlyShowIn {
+            desktop_environment: desktop_environment.clone(),
+        });
+    }
 
-        if not_show_in.contains(desktop_environment) {
+    if not_show_in.contains(desktop_environment) {
+        return Err(SkipReason::NotShowIn {
+            desktop_environment: desktop_environment.clone(),
+        });
+    }
+
+    // the frontend loads the icon by this id through the `cyberdeck-icon` protocol
+    let scale = icons::scale_factor();
+    let resolved_icon = desktop_entry
+        .icon
+        .map(|icon| icon.content)
+        .filter(|icon| !icon.is_empty())
+        .map(|icon| {
+            let path = icons::resolve_icon(&icon, icon_size, scale, icon_theme);
+            (icon, path)
+        });
+    let (icon, icon_path, icon_resolution) = match resolved_icon {
+        Some((icon, Some(path))) => (
+            icon.clone(),
+            path.clone(),
+            IconResolution::Found { icon, path },
+        ),
+        missing_or_not_found => {
             #[cfg(debug_assertions)]
-            {
-                debug!(
-                    "Skipping {app_name}: Explicitly not shown in current desktop environment",
-                    app_name = desktop_entry.name.default
-                );
-            }
-            return None;
+            warn!(
+                "No icon found for {app_name}, using a placeholder",
+                app_name = desktop_entry.name.default
+            );
+            // written by `prepare_application_icon`, so parsing has no side effects
+            let placeholder = placeholder::placeholder_id(desktop_file_id);
+            let resolution = match missing_or_not_found {
+                Some((icon, _)) => IconResolution::NotFound {
+                    icon,
+                    icon_theme: icon_theme::resolve_theme_name(icon_theme),
+                    placeholder: placeholder.clone(),
+                },
+                None => IconResolution::Missing {
+                    placeholder: placeholder.clone(),
+                },
+            };
+            let placeholder_path = placeholder::placeholder_path(&placeholder).unwrap_or_default();
+            (placeholder, placeholder_path, resolution)
         }
+    };
 
-        Some(Application {
-            name: desktop_entry.name.default.clone(),
-            comment: desktop_entry
-                .comment
-                .unwrap_or(LocaleString {
-                    default: String::from(""),
-                    variants: HashMap::new(),
-                })
-                .default,
-            icon: match desktop_entry.icon {
-                Some(icon) => match icon.get_icon_path() {
-                    Some(path) => path.to_string_lossy().into_owned(),
-                    None => {
-                        #[cfg(debug_assertions)]
-                        warn!(
-                            "No icon path found for {app_name}",
-                            app_name = desktop_entry.name.default
-                        );
-                        if !kde_icon_theme.is_empty() {
-                            match freedesktop_icons::lookup(&icon.content)
-                                .with_size(48)
-                                .with_theme(kde_icon_theme)
-                                .find()
-                            {
-                                Some(icon_path) => icon_path.to_string_lossy().into_owned(),
-                                None => String::from(""),
-                            }
-                        } else {
-                            String::from("")
-                        }
-                    }
-                },
-                None => {
-                    #[cfg(debug_assertions)]
-                    warn!(
-                        "No icon found for {app_name}",
-                        app_name = desktop_entry.name.default
-                    );
-                    String::from("")
-                }
-            },
-            exec: app_exec,
-        })
-    } else {
-        #[cfg(debug_assertions)]
-        {
-            debug!("Skipping {file_path}: Not an application entry");
+    let application = Application {
+        id: file_path.clone(),
+        name: desktop_entry.name.default.clone(),
+        comment: desktop_entry
+            .comment
+            .unwrap_or(LocaleString {
+                default: String::from(""),
+                variants: HashMap::new(),
+            })
+            .default,
+        icon,
+        icon_path: icon_path.to_string_lossy().into_owned(),
+        exec: app_exec,
+        categories: application.categories.clone().unwrap_or_default(),
+        terminal: application.terminal.unwrap_or(false),
+        try_exec: application.try_exec.clone(),
+        search_key,
+        frecency: 0.0,
+    };
+    Ok((application, icon_resolution))
+}
+
+// writes the placeholder and rasterises the icon of a parsed application during
+// the scan, `parse_application_from_file` leaves the cache alone for the doctor
+fn prepare_application_icon(application: &mut Application, size: u32) {
+    if let Some(app_id) = application
+        .icon
+        .strip_prefix(placeholder::PLACEHOLDER_PREFIX)
+    {
+        if placeholder::create_placeholder(app_id, &application.name).is_none() {
+            application.icon.clear();
+            application.icon_path.clear();
+            return;
         }
-        None
+    }
+    // rasterised while the scan is running in parallel anyway,
+    // so opening the launcher only reads small PNGs
+    if let Some(cached) = icon_cache::get_cached_icon(Path::new(&application.icon_path), size) {
+        application.icon_path = cached.to_string_lossy().into_owned();
     }
 }
 
-async fn clean_exec_command(exec: String, app_name: &str) -> String {
+/// Like `TryExec` is meant: an absolute path or a name looked up in `$PATH`,
+/// either has to be an executable file.
+pub fn is_program_installed(program: &str) -> bool {
+    let is_executable = |path: &Path| {
+        std::fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+    if program.contains('/') {
+        return is_executable(Path::new(program));
+    }
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| is_executable(&dir.join(program)))
+    })
+}
+
+fn clean_exec_command(exec: String, app_name: &str) -> String {
     // is a separate function because at first I decided to remove some args,
     // like `%U` and `%f`, but then decided not to use them at all because of
     // some weird results like:
@@ -356,27 +658,3 @@ async fn clean_exec_command(exec: String, app_name: &str) -> String {
         result.join(" ")
     }
 }
-
-pub async fn cache_apps(apps: &Vec<Application>) -> std::io::Result<()> {
-    let cache_path = format!(
-        "{}/.local/share/cyberdeck/apps_cache.json",
-        env::var("HOME").unwrap_or_else(|_| String::from("/home"))
-    );
-    if let Some(parent) = Path::new(&cache_path).parent() {
-        fs::create_dir_all(parent)?;
-    }
-    let json = serde_json::to_string(apps)
-        .map_err(|e| std::io::Error::other(format!("Serialization error: {e}")))?;
-    fs::write(cache_path, json)
-}
-
-pub async fn read_cached_apps() -> std::io::Result<Vec<Application>> {
-    let cache_path = format!(
-        "{}/.local/share/cyberdeck/apps_cache.json",
-        env::var("HOME").unwrap_or_else(|_| String::from("/home"))
-    );
-    let data = fs::read_to_string(cache_path)?;
-    let apps: Vec<Application> = serde_json::from_str(&data)
-        .map_err(|e| std::io::Error::other(format!("Deserialization error: {e}")))?;
-    Ok(apps)
-}
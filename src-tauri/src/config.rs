@@ -1,39 +1,310 @@
-use log::{error, info};
-use once_cell::sync::Lazy;
+use crate::app_index::CacheConfig;
+use crate::app_watcher;
+use crate::config_file::{self, ConfigFormat};
+use crate::icons;
+use crate::keybindings::{Action, KeybindingsConfig};
+use crate::layers::{self, ConfigLayer, ConfigSource, InvalidValue, MergedConfig};
+use crate::migrations;
+use crate::palette::{self, PaletteConfig};
+use crate::paths;
+use crate::theme;
+use log::{error, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use tauri::Emitter;
 use ts_rs::TS;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// every missing field falls back to its value from `CyberdeckConfig::default()`,
+// so a config written by an older version still loads after new options are added
+#[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/types/CyberdeckConfig.ts")]
 pub struct CyberdeckConfig {
-    pub apps_per_page: u16,
+    /// Schema version of the config file, used to migrate it after updates
+    pub version: u32,
+    /// Terminal that runs applications with `Terminal=true`
     pub terminal_app: String,
+    /// Compared against `OnlyShowIn` and `NotShowIn` of desktop entries
     pub desktop_environment: String,
-    pub kde_icon_theme: String,
-    pub lookup_dirs: Vec<String>,
+    /// Icon theme to look icons up in, empty to detect it from the GTK, GNOME or KDE settings
+    pub icon_theme: String,
+    /// Directories scanned recursively for .desktop files
+    pub lookup_dirs: LookupDirs,
+    /// Look and behaviour of the launcher window
+    pub ui: UiConfig,
+    /// Key chords mapped to launcher actions
+    pub keybindings: KeybindingsConfig,
+    /// Colours generated from the wallpaper, exposed as CSS variables
+    pub palette: PaletteConfig,
+    /// How the application index is stored between runs
+    pub cache: CacheConfig,
 }
 
 impl Default for CyberdeckConfig {
     fn default() -> Self {
         CyberdeckConfig {
-            apps_per_page: 5,
+            version: migrations::CURRENT_CONFIG_VERSION,
             terminal_app: "kitty".to_string(),
             desktop_environment: "Hyprland".to_string(),
-            kde_icon_theme: "".to_string(),
-            lookup_dirs: vec![
-                String::from("/usr/share/applications/"),
-                String::from("/var/lib/flatpak/exports/share/applications/"),
-            ],
+            icon_theme: "".to_string(),
+            lookup_dirs: LookupDirs::default(),
+            ui: UiConfig::default(),
+            keybindings: KeybindingsConfig::default(),
+            palette: PaletteConfig::default(),
+            cache: CacheConfig::default(),
+        }
+    }
+}
+
+impl CyberdeckConfig {
+    /// Checks the values serde can't, like ranges and empty strings.
+    pub fn validate(&self) -> Result<(), String> {
+        match self.invalid_values().into_iter().next() {
+            Some(invalid) => Err(invalid.message),
+            None => Ok(()),
+        }
+    }
+
+    /// Every value that deserializes but can't be used.
+    pub fn invalid_values(&self) -> Vec<InvalidValue> {
+        let mut invalid = self.ui.invalid_values();
+        invalid.extend(self.keybindings.invalid_values());
+        invalid.extend(self.palette.invalid_values());
+        if self.terminal_app.trim().is_empty() {
+            invalid.push(InvalidValue::new(
+                &["terminal_app"],
+                String::from("`terminal_app` must not be empty"),
+            ));
+        }
+        for (name, dirs) in [
+            ("override", &self.lookup_dirs.override_dirs),
+            ("extend", &self.lookup_dirs.extend),
+        ] {
+            if let Some(dir) = dirs.iter().find(|dir| dir.trim().is_empty()) {
+                invalid.push(InvalidValue::new(
+                    &["lookup_dirs", name],
+                    format!("`lookup_dirs.{name}` contains an empty directory: {dir:?}"),
+                ));
+            }
+        }
+        invalid
+    }
+}
+
+const DEFAULT_LOOKUP_DIRS: [&str; 2] = [
+    "/usr/share/applications/",
+    "/var/lib/flatpak/exports/share/applications/",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, TS, JsonSchema)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/types/LookupDirs.ts")]
+pub struct LookupDirs {
+    /// searched in addition to the default directories
+    pub extend: Vec<String>,
+    /// replaces the default directories when not empty
+    #[serde(rename = "override")]
+    pub override_dirs: Vec<String>,
+}
+
+impl LookupDirs {
+    pub fn resolve(&self) -> Vec<String> {
+        let mut dirs: Vec<String> = if self.override_dirs.is_empty() {
+            DEFAULT_LOOKUP_DIRS
+                .iter()
+                .map(|dir| dir.to_string())
+                .collect()
+        } else {
+            self.override_dirs.clone()
+        };
+        for dir in &self.extend {
+            if !dirs.contains(dir) {
+                dirs.push(dir.clone());
+            }
         }
+        dirs
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/Layout.ts")]
+pub enum Layout {
+    /// one application per row
+    List,
+    /// `grid_columns` x `grid_rows` tiles per page
+    Grid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/Mode.ts")]
+pub enum Mode {
+    /// search installed applications
+    Apps,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/types/UiConfig.ts")]
+pub struct UiConfig {
+    /// Number of applications shown on a single page of the list layout
+    #[schemars(range(min = 1))]
+    pub apps_per_page: u16,
+    pub layout: Layout,
+    /// Columns of the grid layout
+    #[schemars(range(min = 1))]
+    pub grid_columns: u16,
+    /// Rows of the grid layout
+    #[schemars(range(min = 1))]
+    pub grid_rows: u16,
+    /// Maximum number of search results, 0 for no limit
+    pub result_limit: u32,
+    /// Text shown in the empty search field
+    pub placeholder: String,
+    /// Mode the launcher opens in
+    pub default_mode: Mode,
+    /// Close the launcher when it loses keyboard focus
+    pub close_on_focus_loss: bool,
+    /// Keep the launcher open after starting an application
+    pub keep_open_after_launch: bool,
+    /// Animate selection and page changes
+    pub animations: bool,
+    /// Icon size in logical pixels, multiplied by the screen's scale factor
+    #[schemars(range(min = 1, max = 1024))]
+    pub icon_size: u16,
+    /// Directory in `themes/` next to the user config loaded on top of the built-in style,
+    /// empty for none
+    pub theme: String,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            apps_per_page: 5,
+            layout: Layout::List,
+            grid_columns: 4,
+            grid_rows: 3,
+            result_limit: 0,
+            placeholder: String::from("Type a command..."),
+            default_mode: Mode::Apps,
+            close_on_focus_loss: false,
+            keep_open_after_launch: false,
+            animations: true,
+            icon_size: icons::DEFAULT_ICON_SIZE,
+            theme: String::new(),
+        }
+    }
+}
+
+impl UiConfig {
+    pub fn invalid_values(&self) -> Vec<InvalidValue> {
+        let mut invalid = vec![];
+        for (name, value) in [
+            ("apps_per_page", self.apps_per_page),
+            ("grid_columns", self.grid_columns),
+            ("grid_rows", self.grid_rows),
+        ] {
+            if value == 0 {
+                invalid.push(InvalidValue::new(
+                    &["ui", name],
+                    format!("`ui.{name}` must be at least 1"),
+                ));
+            }
+        }
+        if !(1..=1024).contains(&self.icon_size) {
+            invalid.push(InvalidValue::new(
+                &["ui", "icon_size"],
+                String::from("`ui.icon_size` must be between 1 and 1024"),
+            ));
+        }
+        if !self.theme.is_empty() && !theme::is_theme_name(&self.theme) {
+            invalid.push(InvalidValue::new(
+                &["ui", "theme"],
+                format!("`ui.theme` must be a directory name, got {:?}", self.theme),
+            ));
+        }
+        invalid
+    }
+
+    /// Number of applications on a single page of the current layout.
+    pub fn page_size(&self) -> u16 {
+        match self.layout {
+            Layout::List => self.apps_per_page,
+            Layout::Grid => self.grid_columns.saturating_mul(self.grid_rows),
+        }
+    }
+}
+
+/// The part of the config the frontend needs, resolved for the current layout.
 #[derive(Debug, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/types/ClientConfig.ts")]
 pub struct ClientConfig {
+    /// applications on a single page of the current layout
     pub apps_per_page: u16,
+    pub layout: Layout,
+    pub grid_columns: u16,
+    pub result_limit: u32,
+    pub placeholder: String,
+    pub default_mode: Mode,
+    pub close_on_focus_loss: bool,
+    pub keep_open_after_launch: bool,
+    pub animations: bool,
+    /// requested from the `cyberdeck-icon` protocol
+    pub icon_size: u16,
+    /// active theme, its files are served through the `cyberdeck-theme` protocol
+    pub theme: String,
+    /// canonical key chord, e.g. "Ctrl+Shift+k", -> action
+    pub keybindings: BTreeMap<String, Action>,
+}
+
+impl From<&CyberdeckConfig> for ClientConfig {
+    fn from(config: &CyberdeckConfig) -> Self {
+        let ui = &config.ui;
+        let (keybindings, errors) = config.keybindings.resolve();
+        for e in errors {
+            warn!("Skipping key binding: {e}");
+        }
+        ClientConfig {
+            apps_per_page: ui.page_size(),
+            layout: ui.layout,
+            grid_columns: ui.grid_columns,
+            result_limit: ui.result_limit,
+            placeholder: ui.placeholder.clone(),
+            default_mode: ui.default_mode,
+            close_on_focus_loss: ui.close_on_focus_loss,
+            keep_open_after_launch: ui.keep_open_after_launch,
+            animations: ui.animations,
+            icon_size: ui.icon_size,
+            theme: ui.theme.clone(),
+            keybindings,
+        }
+    }
+}
+
+/// Selected on the command line, must be set before `APP_CONFIG` is first used.
+#[derive(Debug, Default, Clone)]
+pub struct ConfigOptions {
+    pub profile: Option<String>,
+    pub overrides: Vec<String>,
+    /// Writes the default config when there is no user config yet. Off for the
+    /// headless subcommands, which only read the config
+    pub create_user_config: bool,
+}
+
+static CONFIG_OPTIONS: OnceCell<ConfigOptions> = OnceCell::new();
+
+pub fn set_config_options(options: ConfigOptions) {
+    if CONFIG_OPTIONS.set(options).is_err() {
+        warn!("Config options were already set, ignoring");
+    }
 }
 
 pub static APP_CONFIG: Lazy<Mutex<CyberdeckConfig>> = Lazy::new(|| {
@@ -45,77 +316,450 @@ pub static APP_CONFIG: Lazy<Mutex<CyberdeckConfig>> = Lazy::new(|| {
     Mutex::new(config)
 });
 
-fn get_cyberdeck_config_path() -> Result<PathBuf, String> {
-    let home =
-        std::env::var("HOME").map_err(|e| format!("HOME environment variable not set: {e}"))?;
-    let path = format!("{home}/.config/cyberdeck/config.json");
-    Ok(PathBuf::from(path))
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "../../src/types/EffectiveConfig.ts")]
+pub struct EffectiveConfig {
+    pub config: CyberdeckConfig,
+    /// dotted key path -> where its value came from
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+// file stem looked up as `config.toml`, `config.jsonc` and `config.json`
+const CONFIG_FILE_STEM: &str = "config";
+
+// `$XDG_CONFIG_DIRS` is ordered from the most to the least important directory
+fn get_system_config_paths() -> Vec<PathBuf> {
+    let config_dirs = std::env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| String::from("/etc/xdg"));
+    config_dirs
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .rev()
+        .filter_map(|dir| {
+            config_file::find_config_file(&PathBuf::from(dir).join("cyberdeck"), CONFIG_FILE_STEM)
+        })
+        .collect()
+}
+
+/// The existing user config, or where a new one would be created.
+fn get_user_config_path() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| {
+        config_file::find_config_file(&dir, CONFIG_FILE_STEM)
+            .unwrap_or_else(|| dir.join(format!("{CONFIG_FILE_STEM}.toml")))
+    })
+}
+
+fn get_profile_config_path(profile: &str) -> Option<PathBuf> {
+    let profiles_dir = paths::config_dir()?.join("profiles");
+    config_file::find_config_file(&profiles_dir, profile)
 }
 
 pub fn load_or_create_config() -> Result<CyberdeckConfig, String> {
-    let config_path = get_cyberdeck_config_path()?;
-    info!("Attempting to load config from {config_path:?}");
-    if config_path.exists() {
-        let config_string = fs::read_to_string(&config_path)
-            .map_err(|e| format!("Failed to parse config at {config_path:?}: {e}"))?;
-        serde_json::from_str(&config_string)
-            .map_err(|e| format!("Failed to parse config from JSON at {config_path:?}: {e}"))
-    } else {
-        let mut default_config = CyberdeckConfig::default();
-        default_config.kde_icon_theme = match get_kde_icon_theme() {
-            Some(icon_theme) => icon_theme,
-            None => default_config.kde_icon_theme,
-        };
+    Ok(load_effective_config()?.config)
+}
 
-        println!("Config file does not exist. Creating default at {config_path:?}",);
+/// Merges, from the lowest to the highest precedence: defaults, system configs,
+/// the user config, the selected profile, `CYBERDECK_*` variables and `--set` arguments.
+pub fn load_effective_config() -> Result<EffectiveConfig, String> {
+    let defaults = default_config_value()?;
+    let mut merged = layers::merge_layers(load_config_layers(&defaults)?);
+    let config = deserialize_merged(&mut merged, &defaults)?;
 
-        if let Some(parent) = config_path.parent() {
-            match fs::create_dir_all(parent) {
-                Ok(path) => info!("Created config directory: {path:?}"),
-                Err(e) => error!("Error while creating config directory: {e}"),
-            }
+    Ok(EffectiveConfig {
+        config,
+        sources: merged.sources,
+    })
+}
+
+fn default_config_value() -> Result<Value, String> {
+    serde_json::to_value(CyberdeckConfig::default())
+        .map_err(|e| format!("Failed to serialize default config: {e}"))
+}
+
+/// Every layer of the config, from the lowest to the highest precedence.
+fn load_config_layers(defaults: &Value) -> Result<Vec<ConfigLayer>, String> {
+    let options = CONFIG_OPTIONS.get().cloned().unwrap_or_default();
+    let mut config_layers = vec![ConfigLayer {
+        source: ConfigSource::Default,
+        value: defaults.clone(),
+    }];
+
+    for path in get_system_config_paths() {
+        info!("Loading system config from {path:?}");
+        config_layers.push(ConfigLayer {
+            value: read_config_layer(&path, false)?,
+            source: ConfigSource::System { path },
+        });
+    }
+    let has_system_config = config_layers.len() > 1;
+
+    match get_user_config_path() {
+        Some(path) if path.exists() => {
+            info!("Attempting to load config from {path:?}");
+            config_layers.push(ConfigLayer {
+                value: read_config_layer(&path, true)?,
+                source: ConfigSource::User { path },
+            });
         }
-        match fs::write(
-            config_path,
-            serde_json::to_string_pretty(&default_config).expect("Failed to serialize struct"),
-        ) {
-            Ok(_) => info!("Config written to file succesfully"),
-            Err(e) => error!("Error while writing to config file {e}"),
-        }
-        Ok(default_config)
-    }
-}
-
-fn get_kde_icon_theme() -> Option<String> {
-    let output_result = std::process::Command::new("kreadconfig5")
-        .args(["--file", "kdeglobals", "--group", "Icons", "--key", "Theme"])
-        .output();
-    match output_result {
-        Ok(output) => {
-            if output.status.success() {
-                let theme = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                if !theme.is_empty() {
-                    Some(theme)
-                } else {
-                    #[cfg(debug_assertions)]
-                    error!("kreadconfig5 returned empty theme");
-                    None
-                }
-            } else {
-                #[cfg(debug_assertions)]
-                error!(
-                    "kreadconfig5 failed: {stderr}",
-                    stderr = String::from_utf8_lossy(&output.stderr)
-                );
-                None
-            }
+        Some(path) if options.create_user_config => config_layers.push(ConfigLayer {
+            value: create_default_config(&path, has_system_config),
+            source: ConfigSource::User { path },
+        }),
+        Some(path) => info!("No config at {path:?}, using the defaults"),
+        None => warn!("Neither XDG_CONFIG_HOME nor HOME is set, skipping user config"),
+    }
+
+    if let Some(name) = options.profile {
+        let path = get_profile_config_path(&name)
+            .ok_or_else(|| format!("Profile '{name}' not found in the config directory"))?;
+        info!("Loading profile '{name}' from {path:?}");
+        config_layers.push(ConfigLayer {
+            value: read_config_layer(&path, true)?,
+            source: ConfigSource::Profile { name, path },
+        });
+    }
+
+    config_layers.extend(layers::env_layers(defaults));
+    config_layers.extend(layers::cli_layers(&options.overrides, defaults));
+    Ok(config_layers)
+}
+
+/// Deserializes the merged layers. A value of the wrong type or one that fails
+/// validation falls back to its default, so one bad key doesn't discard the others.
+fn deserialize_merged(
+    merged: &mut MergedConfig,
+    defaults: &Value,
+) -> Result<CyberdeckConfig, String> {
+    loop {
+        let invalid =
+            match serde_path_to_error::deserialize::<_, CyberdeckConfig>(merged.value.clone()) {
+                Ok(config) => match config.invalid_values().into_iter().next() {
+                    Some(invalid) => invalid,
+                    None => return Ok(config),
+                },
+                Err(e) => InvalidValue {
+                    // a bad element falls back with its whole list
+                    key: e
+                        .path()
+                        .iter()
+                        .map_while(|segment| match segment {
+                            Segment::Map { key } => Some(key.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    message: e.inner().to_string(),
+                },
+            };
+        let key = invalid.key.join(".");
+        let source = merged
+            .sources
+            .get(&key)
+            .map_or_else(|| String::from("unknown source"), |s| s.to_string());
+        if !layers::reset_to_default(merged, &invalid.key, defaults) {
+            return Err(format!(
+                "Invalid value for `{key}` from {source}: {message}",
+                message = invalid.message
+            ));
         }
-        Err(_e) => {
-            #[cfg(debug_assertions)]
-            error!("Failed to execute kreadconfig5: {_e}");
-            None
+        warn!(
+            "Invalid value for `{key}` from {source}, using the default: {message}",
+            message = invalid.message
+        );
+    }
+}
+
+/// Writes the default config for a new user and returns what was written.
+/// When a system config exists, only the version is written, so the system-wide
+/// values are not shadowed by defaults.
+fn create_default_config(config_path: &Path, has_system_config: bool) -> Value {
+    let (default_value, default_toml) = if has_system_config {
+        let version = migrations::CURRENT_CONFIG_VERSION;
+        (
+            serde_json::json!({ "version": version }),
+            format!(
+                "{DEFAULT_CONFIG_HEADER}\n\
+                # Schema version of this file, used to migrate it after updates. Do not edit.\n\
+                version = {version}\n"
+            ),
+        )
+    } else {
+        let default_config = CyberdeckConfig::default();
+        (
+            serde_json::to_value(&default_config).expect("Failed to serialize struct"),
+            default_config_toml(&default_config),
+        )
+    };
+
+    info!("Config file does not exist. Creating default at {config_path:?}");
+
+    if let Some(parent) = config_path.parent() {
+        match fs::create_dir_all(parent) {
+            Ok(path) => info!("Created config directory: {path:?}"),
+            Err(e) => error!("Error while creating config directory: {e}"),
         }
     }
+    match config_file::write_atomically(config_path, default_toml) {
+        Ok(_) => info!("Config written to file succesfully"),
+        Err(e) => error!("Error while writing to config file {e}"),
+    }
+    default_value
+}
+
+const DEFAULT_CONFIG_HEADER: &str = "\
+# Cyberdeck configuration
+#
+# Every option may be left out, a missing one falls back to its default.
+# Values from $XDG_CONFIG_DIRS/cyberdeck/config.toml (system-wide) are applied first,
+# this file and `profiles/<name>.toml` (selected with --profile) override them, and
+# CYBERDECK_* environment variables or `--set key=value` arguments override everything.
+";
+
+// when adding an option, document it here as well
+fn default_config_toml(config: &CyberdeckConfig) -> String {
+    let default_dirs = DEFAULT_LOOKUP_DIRS.join(", ");
+    format!(
+        r#"{DEFAULT_CONFIG_HEADER}
+# Schema version of this file, used to migrate it after updates. Do not edit.
+version = {version}
+
+# Terminal that runs applications with `Terminal=true`, e.g. "kitty" or "foot".
+terminal_app = {terminal_app}
+
+# Compared against `OnlyShowIn` and `NotShowIn` of desktop entries.
+desktop_environment = {desktop_environment}
+
+# Icon theme to look icons up in, falling back to the themes it inherits from and "hicolor".
+# Leave empty to use the theme of gsettings, gtk-3.0/settings.ini or kdeglobals.
+icon_theme = {icon_theme}
+
+# Look and behaviour of the launcher window.
+[ui]
+# Number of applications shown on a single page of the list layout.
+apps_per_page = {apps_per_page}
+# "list" shows one application per row, "grid" shows grid_columns x grid_rows tiles.
+layout = {layout}
+grid_columns = {grid_columns}
+grid_rows = {grid_rows}
+# Maximum number of search results, 0 for no limit.
+result_limit = {result_limit}
+# Text shown in the empty search field.
+placeholder = {placeholder}
+# Mode the launcher opens in, "apps" searches applications.
+default_mode = {default_mode}
+# Close the launcher when it loses keyboard focus.
+close_on_focus_loss = {close_on_focus_loss}
+# Keep the launcher open after starting an application.
+keep_open_after_launch = {keep_open_after_launch}
+# Animate selection and page changes.
+animations = {animations}
+# Icon size in logical pixels. Icons are looked up for the screen's scale factor,
+# preferring scalable SVGs, so they stay sharp on HiDPI screens.
+icon_size = {icon_size}
+# Theme loaded from themes/<name>/theme.css next to this file, together with the fonts
+# and images it references. Edits are applied while the launcher is open.
+# Leave empty for the built-in style, or pick one for a single run with --theme.
+theme = {theme}
+
+# Key bindings: the preset provides the defaults, `bindings` adds to or overrides them.
+[keybindings]
+# "default", "vim" (adds Ctrl+j/k/h/l, Ctrl+f/b) or "emacs" (adds Ctrl+n/p, Ctrl+v/Alt+v,
+# Ctrl+m, Ctrl+g). The defaults are the arrows, PageUp/PageDown, Enter to launch,
+# Ctrl+Enter to launch in the terminal and Escape to hide.
+preset = {keybindings_preset}
+
+# Maps a key chord to an action, e.g. "Ctrl+Shift+k" = "prev_page".
+# Modifiers are Ctrl, Alt, Shift and Super; keys are single characters or names like
+# Enter, Escape, Tab, Space, ArrowUp, PageDown or F5. Characters are the keys of a US
+# keyboard whatever the layout, so "Ctrl+!" is the same as "Ctrl+Shift+1".
+# Actions: next, prev, next_page, prev_page, launch, launch_in_terminal, hide,
+# or "unbound" to remove a binding of the preset.
+[keybindings.bindings]
+{keybindings_bindings}
+# Colours generated from the wallpaper, set as --palette-* CSS variables and updated
+# whenever the file changes. Every source sets --palette-background, --palette-foreground
+# and --palette-accent, plus its own colours like --palette-color4 or --palette-base0d.
+# The built-in style uses them for the panel background, the text (--red-text in
+# styles.css) and the accent; a theme can use any of the variables.
+[palette]
+# "none", "pywal", "base16" or "matugen".
+source = {palette_source}
+# YAML or JSON palette file, required for base16 and matugen.
+# pywal reads ~/.cache/wal/colors.json unless a path is given.
+path = {palette_path}
+# "dark" or "light", the scheme picked from a matugen palette.
+scheme = {palette_scheme}
+
+# Applications found by the last scan, so the launcher opens without scanning.
+[cache]
+# "json" or "binary". The binary index is smaller and loads faster.
+# Switching the format builds a new index on the next start.
+format = {cache_format}
+
+# Directories scanned recursively for .desktop files. When several have a desktop file
+# with the same id, the one below $XDG_DATA_HOME (e.g. ~/.local/share/applications)
+# is used, otherwise the one of the earlier directory.
+[lookup_dirs]
+# Scanned in addition to the default directories.
+extend = {extend}
+# Replaces the default directories when not empty.
+# The defaults are: {default_dirs}
+override = {override_dirs}
+"#,
+        version = config.version,
+        apps_per_page = config.ui.apps_per_page,
+        layout = toml_literal(&config.ui.layout),
+        grid_columns = config.ui.grid_columns,
+        grid_rows = config.ui.grid_rows,
+        result_limit = config.ui.result_limit,
+        placeholder = toml_literal(&config.ui.placeholder),
+        default_mode = toml_literal(&config.ui.default_mode),
+        close_on_focus_loss = config.ui.close_on_focus_loss,
+        keep_open_after_launch = config.ui.keep_open_after_launch,
+        animations = config.ui.animations,
+        icon_size = config.ui.icon_size,
+        theme = toml_literal(&config.ui.theme),
+        keybindings_preset = toml_literal(&config.keybindings.preset),
+        keybindings_bindings = config
+            .keybindings
+            .bindings
+            .iter()
+            .map(|(chord, action)| format!("{} = {}\n", toml_literal(chord), toml_literal(action)))
+            .collect::<String>(),
+        palette_source = toml_literal(&config.palette.source),
+        palette_path = toml_literal(&config.palette.path),
+        palette_scheme = toml_literal(&config.palette.scheme),
+        cache_format = toml_literal(&config.cache.format),
+        terminal_app = toml_literal(&config.terminal_app),
+        desktop_environment = toml_literal(&config.desktop_environment),
+        icon_theme = toml_literal(&config.icon_theme),
+        extend = toml_literal(&config.lookup_dirs.extend),
+        override_dirs = toml_literal(&config.lookup_dirs.override_dirs),
+    )
+}
+
+fn toml_literal<T: Serialize>(value: &T) -> String {
+    toml::Value::try_from(value)
+        .expect("Failed to serialize config value")
+        .to_string()
+}
+
+/// JSON Schema of the config file, printed by `cyberdeck config schema`.
+pub fn config_json_schema() -> String {
+    let schema = schemars::schema_for!(CyberdeckConfig);
+    serde_json::to_string_pretty(&schema).expect("Failed to serialize config schema")
+}
+
+/// Reads a single config file, returning only the keys it sets.
+fn read_config_layer(config_path: &Path, persist_migration: bool) -> Result<Value, String> {
+    let format = get_config_format(config_path)?;
+    let config_string = read_config_file(config_path, persist_migration)?;
+    // warns about unknown keys; values of the wrong type fall back one by one
+    // once the layers are merged, where a higher layer may still override them
+    parse_config(&config_string, config_path).ok();
+    format
+        .to_value(&config_string)
+        .map_err(|e| format!("Failed to parse config at {config_path:?}: {e}"))
+}
+
+fn get_config_format(config_path: &Path) -> Result<ConfigFormat, String> {
+    ConfigFormat::from_path(config_path)
+        .ok_or_else(|| format!("Unsupported config format of {config_path:?}"))
+}
+
+/// Reads the config file, upgrading it to the current version first if needed.
+/// With `persist_migration`, the original file is backed up and the migrated one
+/// is written over it, otherwise the migration only happens in memory.
+/// Only the migrated keys are rewritten, comments and formatting of the rest stay.
+fn read_config_file(config_path: &Path, persist_migration: bool) -> Result<String, String> {
+    let format = get_config_format(config_path)?;
+    let config_string = fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read config at {config_path:?}: {e}"))?;
+    let original_value = format
+        .to_value(&config_string)
+        .map_err(|e| format!("Failed to parse config at {config_path:?}: {e}"))?;
+
+    let mut config_value = original_value.clone();
+    let Some(old_version) = migrations::migrate(&mut config_value)? else {
+        return Ok(config_string);
+    };
+
+    let edits = config_file::diff_values(&original_value, &config_value);
+    let migrated_string = config_file::apply_edits(format, &config_string, &edits)
+        .map_err(|e| format!("Failed to migrate config at {config_path:?}: {e}"))?;
+    if !persist_migration {
+        return Ok(migrated_string);
+    }
+    let backup_path = migrations::backup_config_file(config_path, old_version)?;
+    info!("Backed up config version {old_version} to {backup_path:?}");
+    config_file::write_atomically(config_path, &migrated_string)
+        .map_err(|e| format!("Failed to write migrated config to {config_path:?}: {e}"))?;
+    info!(
+        "Migrated {config_path:?} to version {version}",
+        version = migrations::CURRENT_CONFIG_VERSION
+    );
+
+    Ok(migrated_string)
+}
+
+/// Deserializes the config, warning about unknown keys instead of rejecting them.
+/// Type errors report the offending key path along with its line and column.
+fn parse_config(config_string: &str, config_path: &Path) -> Result<CyberdeckConfig, String> {
+    let mut unknown_keys: Vec<String> = vec![];
+    let config = get_config_format(config_path)?
+        .deserialize::<CyberdeckConfig>(config_string, &mut unknown_keys);
+
+    // `$schema` points editors to the output of `cyberdeck config schema`
+    for key in unknown_keys.iter().filter(|key| *key != "$schema") {
+        warn!("Unknown key `{key}` in {config_path:?}, ignoring it");
+    }
+
+    config.map_err(|e| format!("Failed to parse config at {config_path:?}: {e}"))
+}
+
+/// Adds every option missing from the config file with its default value.
+/// Existing values and unknown keys are left untouched.
+/// Returns the names of the keys that were added.
+pub fn merge_defaults_into_config_file() -> Result<Vec<String>, String> {
+    let config_path = get_user_config_path()
+        .ok_or_else(|| String::from("Neither XDG_CONFIG_HOME nor HOME is set"))?;
+    let format = get_config_format(&config_path)?;
+    let config_string = read_config_file(&config_path, true)?;
+    // validates the file before touching it
+    parse_config(&config_string, &config_path)?;
+
+    let file_value = format
+        .to_value(&config_string)
+        .map_err(|e| format!("Failed to parse config at {config_path:?}: {e}"))?;
+    let Value::Object(file_map) = file_value else {
+        return Err(format!("Config at {config_path:?} is not an object"));
+    };
+
+    let default_value = serde_json::to_value(CyberdeckConfig::default())
+        .map_err(|e| format!("Failed to serialize default config: {e}"))?;
+    let Value::Object(default_map) = default_value else {
+        return Err(String::from("Default config is not a JSON object"));
+    };
+
+    let missing: serde_json::Map<String, Value> = default_map
+        .into_iter()
+        .filter(|(key, _)| !file_map.contains_key(key))
+        .collect();
+    let added_keys: Vec<String> = missing.keys().cloned().collect();
+
+    if added_keys.is_empty() {
+        info!("Config at {config_path:?} already contains every option");
+        return Ok(added_keys);
+    }
+
+    let merged_string = config_file::insert_keys(format, &config_string, missing)
+        .map_err(|e| format!("Failed to add defaults to {config_path:?}: {e}"))?;
+    config_file::write_atomically(&config_path, merged_string)
+        .map_err(|e| format!("Failed to write config to {config_path:?}: {e}"))?;
+    info!("Added missing options to {config_path:?}: {added_keys:?}");
+
+    Ok(added_keys)
 }
 
 #[tauri::command]
@@ -128,10 +772,118 @@ pub fn get_cyberdeck_config() -> Result<CyberdeckConfig, String> {
 
 #[tauri::command]
 pub fn get_client_config() -> Result<ClientConfig, String> {
-    Ok(ClientConfig {
-        apps_per_page: APP_CONFIG
+    let config_guard = APP_CONFIG
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {e}"))?;
+    Ok(ClientConfig::from(&*config_guard))
+}
+
+#[tauri::command]
+pub fn merge_config_defaults() -> Result<Vec<String>, String> {
+    merge_defaults_into_config_file()
+}
+
+/// Reloads the config from every layer and reports where each value came from.
+#[tauri::command]
+pub fn get_effective_config() -> Result<EffectiveConfig, String> {
+    load_effective_config()
+}
+
+/// Applies a partial config (e.g. `{"ui": {"apps_per_page": 8}}`) and saves it to the user config.
+/// Keys set by the profile, `CYBERDECK_*` variables or `--set` are rejected, as the
+/// saved value would have no effect.
+#[tauri::command]
+pub fn set_cyberdeck_config(
+    app_handle: tauri::AppHandle,
+    patch: Value,
+) -> Result<CyberdeckConfig, String> {
+    if !patch.is_object() {
+        return Err(String::from("Config patch must be an object"));
+    }
+    // only migrations change the version, a patched one would skip or repeat them
+    if patch.get("version").is_some() {
+        return Err(String::from("Config patch must not set `version`"));
+    }
+
+    let defaults = default_config_value()?;
+    let (mut user_layers, higher_layers): (Vec<_>, Vec<_>) = load_config_layers(&defaults)?
+        .into_iter()
+        .partition(|layer| !layer.source.overrides_user_config());
+
+    let patch_keys = layers::key_paths(&patch);
+    let shadowed: Vec<String> = layers::merge_layers(higher_layers)
+        .sources
+        .into_iter()
+        .filter(|(key, _)| {
+            patch_keys.iter().any(|patch_key| {
+                key == patch_key
+                    || key.starts_with(&format!("{patch_key}."))
+                    || patch_key.starts_with(&format!("{key}."))
+            })
+        })
+        .map(|(key, source)| format!("`{key}` is set by {source}"))
+        .collect();
+    if !shadowed.is_empty() {
+        return Err(format!(
+            "Config patch would be overridden: {}",
+            shadowed.join(", ")
+        ));
+    }
+
+    // validates the patch against what the user config will be before touching the file
+    let config_path = get_user_config_path()
+        .ok_or_else(|| String::from("Neither XDG_CONFIG_HOME nor HOME is set"))?;
+    user_layers.push(ConfigLayer {
+        source: ConfigSource::User {
+            path: config_path.clone(),
+        },
+        value: patch.clone(),
+    });
+    let candidate = layers::merge_layers(user_layers).value;
+    let mut unknown_keys = vec![];
+    let candidate: CyberdeckConfig = ConfigFormat::Json
+        .deserialize(&candidate.to_string(), &mut unknown_keys)
+        .map_err(|e| format!("Invalid config patch: {e}"))?;
+    if !unknown_keys.is_empty() {
+        return Err(format!("Unknown config keys: {}", unknown_keys.join(", ")));
+    }
+    candidate.validate()?;
+
+    let format = get_config_format(&config_path)?;
+    let config_string = if config_path.exists() {
+        read_config_file(&config_path, true)?
+    } else {
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create config directory {parent:?}: {e}"))?;
+        }
+        format
+            .serialize(&serde_json::json!({ "version": migrations::CURRENT_CONFIG_VERSION }))
+            .map_err(|e| format!("Failed to serialize config: {e}"))?
+    };
+    let patched_string = config_file::apply_patch(format, &config_string, &patch)
+        .map_err(|e| format!("Failed to update config at {config_path:?}: {e}"))?;
+    config_file::write_atomically(&config_path, &patched_string)
+        .map_err(|e| format!("Failed to write config to {config_path:?}: {e}"))?;
+    info!("Updated config at {config_path:?}");
+
+    let config = load_or_create_config()?;
+    let previous = std::mem::replace(
+        &mut *APP_CONFIG
             .lock()
-            .map_err(|e| format!("Failed to lock config: {e}"))?
-            .apps_per_page,
-    })
+            .map_err(|e| format!("Failed to lock config: {e}"))?,
+        config.clone(),
+    );
+    if previous.icon_theme != config.icon_theme {
+        icons::forget_resolved_icons();
+    }
+
+    if let Err(e) = app_handle.emit("config-changed", &config) {
+        error!("Failed to notify the frontend about the config change: {e}");
+    }
+    // the palette file and lookup directories may have changed as well
+    palette::watch_palette(app_handle.clone());
+    app_watcher::watch_applications(app_handle);
+
+    Ok(config)
 }
@@ -0,0 +1,816 @@
+use json_comments::CommentSettings;
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    /// JSON with `//` and `/* */` comments
+    Jsonc,
+    Json,
+}
+
+// when several files exist in the same directory, the first one wins
+const LOOKUP_ORDER: [ConfigFormat; 3] =
+    [ConfigFormat::Toml, ConfigFormat::Jsonc, ConfigFormat::Json];
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Option<ConfigFormat> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(ConfigFormat::Toml),
+            "jsonc" => Some(ConfigFormat::Jsonc),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Jsonc => "jsonc",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    pub fn to_value(self, content: &str) -> Result<Value, String> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            ConfigFormat::Jsonc | ConfigFormat::Json => {
+                serde_json::from_str(&strip_comments(content)?).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Comments of the original file are not preserved.
+    pub fn serialize(self, value: &Value) -> Result<String, String> {
+        match self {
+            ConfigFormat::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+            ConfigFormat::Jsonc | ConfigFormat::Json => {
+                serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Deserializes `content`, collecting the paths of keys `T` doesn't know about.
+    /// Errors name the offending key path; the format's own error adds the line and column.
+    pub fn deserialize<T: DeserializeOwned>(
+        self,
+        content: &str,
+        unknown_keys: &mut Vec<String>,
+    ) -> Result<T, String> {
+        match self {
+            ConfigFormat::Toml => {
+                let deserializer = toml::Deserializer::parse(content).map_err(|e| e.to_string())?;
+                deserialize_tracked(deserializer, unknown_keys)
+            }
+            ConfigFormat::Jsonc | ConfigFormat::Json => {
+                // comments are replaced with spaces, so lines and columns still match the file
+                let stripped = strip_comments(content)?;
+                let mut deserializer = serde_json::Deserializer::from_str(&stripped);
+                deserialize_tracked(&mut deserializer, unknown_keys)
+            }
+        }
+    }
+}
+
+fn deserialize_tracked<'de, D, T>(
+    deserializer: D,
+    unknown_keys: &mut Vec<String>,
+) -> Result<T, String>
+where
+    D: serde::Deserializer<'de>,
+    D::Error: Display,
+    T: serde::Deserialize<'de>,
+{
+    let mut on_unknown_key = |path: serde_ignored::Path| unknown_keys.push(path.to_string());
+    let deserializer = serde_ignored::Deserializer::new(deserializer, &mut on_unknown_key);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        format!(
+            "invalid value for `{key}`: {inner}",
+            key = e.path(),
+            inner = e.inner()
+        )
+    })
+}
+
+fn strip_comments(content: &str) -> Result<String, String> {
+    let mut stripped = String::with_capacity(content.len());
+    CommentSettings::c_style()
+        .strip_comments(content.as_bytes())
+        .read_to_string(&mut stripped)
+        .map_err(|e| format!("Failed to strip comments: {e}"))?;
+    Ok(stripped)
+}
+
+/// Looks for `<stem>.toml`, `<stem>.jsonc` and `<stem>.json` in `dir`, in that order.
+pub fn find_config_file(dir: &Path, stem: &str) -> Option<PathBuf> {
+    let mut existing = LOOKUP_ORDER
+        .iter()
+        .map(|format| dir.join(format!("{stem}.{}", format.extension())))
+        .filter(|path| path.exists());
+    let found = existing.next()?;
+    for ignored in existing {
+        warn!("Both {found:?} and {ignored:?} exist, ignoring the latter");
+    }
+    Some(found)
+}
+
+/// A change to a config file, at a key path like `["ui", "apps_per_page"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigEdit {
+    Set(Vec<String>, Value),
+    Remove(Vec<String>),
+}
+
+/// The edits turning `old` into `new`. Objects present in both are compared key by key,
+/// so the rest of a section that changed is left alone.
+pub fn diff_values(old: &Value, new: &Value) -> Vec<ConfigEdit> {
+    let mut edits = vec![];
+    diff_into(&mut vec![], old, new, &mut edits);
+    edits
+}
+
+fn diff_into(path: &mut Vec<String>, old: &Value, new: &Value, edits: &mut Vec<ConfigEdit>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for key in old_map.keys().filter(|key| !new_map.contains_key(*key)) {
+                let mut key_path = path.clone();
+                key_path.push(key.clone());
+                edits.push(ConfigEdit::Remove(key_path));
+            }
+            for (key, new_value) in new_map {
+                path.push(key.clone());
+                match old_map.get(key) {
+                    Some(old_value) => diff_into(path, old_value, new_value, edits),
+                    None => edits.push(ConfigEdit::Set(path.clone(), new_value.clone())),
+                }
+                path.pop();
+            }
+        }
+        (old, new) if old == new => {}
+        (_, new) => edits.push(ConfigEdit::Set(path.clone(), new.clone())),
+    }
+}
+
+/// Applies `edits` to the file content. Comments and formatting of everything
+/// the edits don't touch are kept, in every format.
+pub fn apply_edits(
+    format: ConfigFormat,
+    content: &str,
+    edits: &[ConfigEdit],
+) -> Result<String, String> {
+    match format {
+        ConfigFormat::Toml => {
+            let mut document: toml_edit::DocumentMut =
+                content.parse().map_err(|e| format!("{e}"))?;
+            for edit in edits {
+                edit_toml(document.as_table_mut(), edit)?;
+            }
+            Ok(document.to_string())
+        }
+        ConfigFormat::Jsonc | ConfigFormat::Json => {
+            edits.iter().try_fold(content.to_string(), |content, edit| {
+                edit_json(&content, edit)
+            })
+        }
+    }
+}
+
+/// Adds `missing` top-level keys to the file content, keeping its comments.
+pub fn insert_keys(
+    format: ConfigFormat,
+    content: &str,
+    missing: Map<String, Value>,
+) -> Result<String, String> {
+    let edits: Vec<ConfigEdit> = missing
+        .into_iter()
+        .map(|(key, value)| ConfigEdit::Set(vec![key], value))
+        .collect();
+    apply_edits(format, content, &edits)
+}
+
+/// Deep merges a partial config into the file content: objects are merged key
+/// by key, anything else is replaced, keeping comments and formatting.
+pub fn apply_patch(format: ConfigFormat, content: &str, patch: &Value) -> Result<String, String> {
+    if !patch.is_object() {
+        return Err(String::from("patch is not an object"));
+    }
+    let mut edits = vec![];
+    patch_edits(&mut vec![], patch, &mut edits);
+    apply_edits(format, content, &edits)
+}
+
+fn patch_edits(path: &mut Vec<String>, patch: &Value, edits: &mut Vec<ConfigEdit>) {
+    match patch {
+        Value::Object(patch_map) => {
+            for (key, patch_value) in patch_map {
+                path.push(key.clone());
+                patch_edits(path, patch_value, edits);
+                path.pop();
+            }
+        }
+        _ => edits.push(ConfigEdit::Set(path.clone(), patch.clone())),
+    }
+}
+
+fn edit_toml(root: &mut toml_edit::Table, edit: &ConfigEdit) -> Result<(), String> {
+    let (path, value) = match edit {
+        ConfigEdit::Set(path, value) => (path, Some(value)),
+        ConfigEdit::Remove(path) => (path, None),
+    };
+    let (key, parents) = path
+        .split_last()
+        .ok_or_else(|| String::from("empty key path"))?;
+
+    let mut table: &mut dyn toml_edit::TableLike = root;
+    for parent in parents {
+        if !table.contains_key(parent) {
+            if value.is_none() {
+                return Ok(());
+            }
+            table.insert(parent, toml_edit::Item::Table(toml_edit::Table::new()));
+        }
+        table = table
+            .get_mut(parent)
+            .and_then(toml_edit::Item::as_table_like_mut)
+            .ok_or_else(|| format!("`{parent}` is not a table"))?;
+    }
+
+    let Some(value) = value else {
+        let is_first = table.iter().next().is_some_and(|(name, _)| name == key);
+        let next_key = table
+            .iter()
+            .map(|(name, _)| name)
+            .skip_while(|name| *name != key)
+            .nth(1)
+            .map(str::to_string);
+        let Some(comments) = take_toml_key(table, key) else {
+            return Ok(());
+        };
+        // the comments above a removed key, like the header of the file, move to
+        // the top of the file or the next key
+        match next_key {
+            Some(next_key) if !(is_first && parents.is_empty()) => {
+                if let Some(mut next_key) = table.key_mut(&next_key) {
+                    let decor = next_key.leaf_decor_mut();
+                    let next_comments = raw_str(decor.prefix()).to_string();
+                    decor.set_prefix(format!("{comments}{next_comments}"));
+                }
+            }
+            _ if parents.is_empty() => {
+                let decor = root.decor_mut();
+                let top_comments = raw_str(decor.prefix()).to_string();
+                decor.set_prefix(format!("{top_comments}{comments}"));
+            }
+            _ => {}
+        }
+        return Ok(());
+    };
+
+    let mut item = toml_item(value).map_err(|e| format!("invalid value for `{key}`: {e}"))?;
+    match table.get_mut(key) {
+        // replaced in place, so the comments around it stay as well
+        Some(old_item) if old_item.is_table() == item.is_table() => {
+            match (&mut item, &*old_item) {
+                (toml_edit::Item::Value(new_value), toml_edit::Item::Value(old_value)) => {
+                    *new_value.decor_mut() = old_value.decor().clone();
+                }
+                (toml_edit::Item::Table(new_table), toml_edit::Item::Table(old_table)) => {
+                    *new_table.decor_mut() = old_table.decor().clone();
+                }
+                _ => {}
+            }
+            *old_item = item;
+        }
+        // a value becoming a `[section]` or the other way round
+        Some(_) => {
+            let comments = take_toml_key(table, key);
+            let is_table = item.is_table();
+            table.insert(key, item);
+            if let Some(comments) = comments {
+                if is_table {
+                    if let Some(new_table) =
+                        table.get_mut(key).and_then(toml_edit::Item::as_table_mut)
+                    {
+                        new_table.decor_mut().set_prefix(format!("\n{comments}"));
+                    }
+                } else if let Some(mut new_key) = table.key_mut(key) {
+                    new_key.leaf_decor_mut().set_prefix(comments.trim_start());
+                }
+            }
+        }
+        None => {
+            table.insert(key, item);
+        }
+    }
+    Ok(())
+}
+
+// removes `key`, returning the comments above it
+fn take_toml_key(table: &mut dyn toml_edit::TableLike, key: &str) -> Option<String> {
+    let comments = match table.get(key)? {
+        toml_edit::Item::Table(section) => raw_str(section.decor().prefix()),
+        _ => raw_str(table.key(key)?.leaf_decor().prefix()),
+    }
+    .to_string();
+    table.remove(key);
+    (!comments.trim().is_empty()).then_some(comments)
+}
+
+fn raw_str(raw: Option<&toml_edit::RawString>) -> &str {
+    raw.and_then(toml_edit::RawString::as_str)
+        .unwrap_or_default()
+}
+
+// objects become `[sections]`, placed after the ones already in the file
+fn toml_item(value: &Value) -> Result<toml_edit::Item, String> {
+    match value {
+        Value::Object(map) => {
+            let mut table = toml_edit::Table::new();
+            for (key, value) in map {
+                table.insert(key, toml_item(value)?);
+            }
+            Ok(toml_edit::Item::Table(table))
+        }
+        _ => {
+            let value: toml_edit::Value = toml::Value::try_from(value)
+                .map_err(|e| e.to_string())?
+                .to_string()
+                .parse()
+                .map_err(|e| format!("{e}"))?;
+            Ok(toml_edit::Item::Value(value))
+        }
+    }
+}
+
+// A member of a JSON object, in byte positions of the content with its comments
+// stripped, which are the same as in the original content.
+struct JsonMember {
+    key: String,
+    start: usize,
+    value: Range<usize>,
+}
+
+fn edit_json(content: &str, edit: &ConfigEdit) -> Result<String, String> {
+    let (path, value) = match edit {
+        ConfigEdit::Set(path, value) => (path, Some(value)),
+        ConfigEdit::Remove(path) => (path, None),
+    };
+    let (key, parents) = path
+        .split_last()
+        .ok_or_else(|| String::from("empty key path"))?;
+
+    // working on the stripped copy, commented out braces and keys are not mistaken for real ones
+    let stripped = strip_comments(content)?;
+    let mut open = skip_whitespace(stripped.as_bytes(), 0);
+    if stripped.as_bytes().get(open) != Some(&b'{') {
+        return Err(String::from("config is not a JSON object"));
+    }
+
+    for (depth, parent) in parents.iter().enumerate() {
+        let (members, close) = json_object(&stripped, open)?;
+        let member = members.iter().find(|member| member.key == *parent);
+        match (member, value) {
+            (Some(member), _) if stripped.as_bytes()[member.value.start] == b'{' => {
+                open = member.value.start;
+            }
+            (_, None) => return Ok(content.to_string()),
+            (Some(member), Some(value)) => {
+                let nested = nested_value(&path[depth + 1..], value);
+                return Ok(replace_json_value(content, &stripped, member, &nested));
+            }
+            (None, Some(value)) => {
+                let nested = nested_value(&path[depth + 1..], value);
+                return Ok(insert_json_member(
+                    content, &stripped, &members, open, close, parent, &nested,
+                ));
+            }
+        }
+    }
+
+    let (members, close) = json_object(&stripped, open)?;
+    let member = members.iter().find(|member| member.key == *key);
+    Ok(match (member, value) {
+        (Some(member), Some(value)) => replace_json_value(content, &stripped, member, value),
+        (None, Some(value)) => {
+            insert_json_member(content, &stripped, &members, open, close, key, value)
+        }
+        (Some(member), None) => remove_json_member(content, &stripped, member),
+        (None, None) => content.to_string(),
+    })
+}
+
+// `["a", "b"]` and `1` -> `{"a": {"b": 1}}`
+fn nested_value(keys: &[String], value: &Value) -> Value {
+    keys.iter().rev().fold(value.clone(), |nested, key| {
+        let mut map = Map::new();
+        map.insert(key.clone(), nested);
+        Value::Object(map)
+    })
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+fn json_string_end(bytes: &[u8], start: usize) -> Result<usize, String> {
+    let mut pos = start + 1;
+    while let Some(byte) = bytes.get(pos) {
+        match byte {
+            b'\\' => pos += 2,
+            b'"' => return Ok(pos + 1),
+            _ => pos += 1,
+        }
+    }
+    Err(String::from("unterminated string"))
+}
+
+fn json_value_end(bytes: &[u8], start: usize) -> Result<usize, String> {
+    match bytes.get(start) {
+        Some(b'"') => json_string_end(bytes, start),
+        Some(b'{' | b'[') => {
+            let mut depth = 0;
+            let mut pos = start;
+            while let Some(byte) = bytes.get(pos) {
+                match byte {
+                    b'"' => {
+                        pos = json_string_end(bytes, pos)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+            Err(String::from("unterminated object or array"))
+        }
+        Some(_) => {
+            let mut pos = start;
+            while bytes.get(pos).is_some_and(|byte| {
+                !matches!(byte, b',' | b'}' | b']') && !byte.is_ascii_whitespace()
+            }) {
+                pos += 1;
+            }
+            Ok(pos)
+        }
+        None => Err(String::from("unexpected end of file")),
+    }
+}
+
+// the members of the object opening at `open` and the position of its closing brace
+fn json_object(stripped: &str, open: usize) -> Result<(Vec<JsonMember>, usize), String> {
+    let bytes = stripped.as_bytes();
+    let mut members = vec![];
+    let mut pos = skip_whitespace(bytes, open + 1);
+    loop {
+        match bytes.get(pos) {
+            Some(b'}') => return Ok((members, pos)),
+            Some(b'"') => {}
+            _ => return Err(format!("expected a key at byte {pos}")),
+        }
+        let key_end = json_string_end(bytes, pos)?;
+        let key: String =
+            serde_json::from_str(&stripped[pos..key_end]).map_err(|e| e.to_string())?;
+        let colon = skip_whitespace(bytes, key_end);
+        if bytes.get(colon) != Some(&b':') {
+            return Err(format!("expected `:` after `{key}`"));
+        }
+        let value_start = skip_whitespace(bytes, colon + 1);
+        let value_end = json_value_end(bytes, value_start)?;
+        members.push(JsonMember {
+            key,
+            start: pos,
+            value: value_start..value_end,
+        });
+        pos = skip_whitespace(bytes, value_end);
+        if bytes.get(pos) == Some(&b',') {
+            pos = skip_whitespace(bytes, pos + 1);
+        }
+    }
+}
+
+// the whitespace before `pos` on its line, `None` when something else precedes it
+fn line_indent(stripped: &str, pos: usize) -> Option<&str> {
+    let line_start = stripped[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &stripped[line_start..pos];
+    indent
+        .chars()
+        .all(|c| c == ' ' || c == '\t')
+        .then_some(indent)
+}
+
+fn format_json_value(value: &Value, indent: &str) -> String {
+    serde_json::to_string_pretty(value)
+        .unwrap_or_else(|_| value.to_string())
+        .replace('\n', &format!("\n{indent}"))
+}
+
+fn replace_json_value(content: &str, stripped: &str, member: &JsonMember, value: &Value) -> String {
+    let indent = line_indent(stripped, member.start).unwrap_or_default();
+    format!(
+        "{before}{value}{after}",
+        before = &content[..member.value.start],
+        value = format_json_value(value, indent),
+        after = &content[member.value.end..]
+    )
+}
+
+fn insert_json_member(
+    content: &str,
+    stripped: &str,
+    members: &[JsonMember],
+    open: usize,
+    close: usize,
+    key: &str,
+    value: &Value,
+) -> String {
+    let close_indent = line_indent(stripped, close).unwrap_or_default();
+    let indent = members
+        .first()
+        .and_then(|member| line_indent(stripped, member.start))
+        .map_or_else(|| format!("{close_indent}  "), str::to_string);
+    let last_token_end = stripped[..close].trim_end().len().max(open + 1);
+    let needs_comma = !stripped[..last_token_end].ends_with(['{', ',']);
+
+    // comments after the last value stay where they are
+    format!(
+        "{before}{comma}{comments}\n{indent}{key}: {value}\n{close_indent}{after}",
+        before = &content[..last_token_end],
+        comma = if needs_comma { "," } else { "" },
+        comments = content[last_token_end..close].trim_end(),
+        key = Value::String(key.to_string()),
+        value = format_json_value(value, &indent),
+        after = &content[close..]
+    )
+}
+
+fn remove_json_member(content: &str, stripped: &str, member: &JsonMember) -> String {
+    let bytes = stripped.as_bytes();
+    let line_end = |pos: usize| {
+        stripped[pos..]
+            .find('\n')
+            .map_or(stripped.len(), |i| pos + i)
+    };
+    let comma = skip_whitespace(bytes, member.value.end);
+
+    let (start, end) = if bytes.get(comma) == Some(&b',') {
+        // takes its own line along, when it has one
+        let rest_end = line_end(comma + 1);
+        match line_indent(stripped, member.start) {
+            Some(indent) if stripped[comma + 1..rest_end].trim().is_empty() => (
+                member.start - indent.len(),
+                (rest_end + 1).min(stripped.len()),
+            ),
+            _ => (member.start, comma + 1),
+        }
+    } else {
+        // the last member takes the comma before it
+        let before = stripped[..member.start].trim_end();
+        let start = match before.strip_suffix(',') {
+            Some(before_comma) => before_comma.len(),
+            None => member.start,
+        };
+        let rest_end = line_end(member.value.end);
+        if stripped[member.value.end..rest_end].trim().is_empty() {
+            (start, rest_end)
+        } else {
+            (start, member.value.end)
+        }
+    };
+    format!("{}{}", &content[..start], &content[end..])
+}
+
+/// Writes to a temporary file next to `path` and renames it over the original,
+/// so readers never see a partially written file.
+pub fn write_atomically(path: &Path, content: impl AsRef<[u8]>) -> std::io::Result<()> {
+    // unique, so concurrent writers of the same file don't share a temporary file
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(
+        ".{pid}-{count}.tmp",
+        pid = std::process::id(),
+        count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(content.as_ref())?;
+    file.sync_all()?;
+    std::fs::rename(&temp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn apply_patch_keeps_toml_comments() {
+        let content =
+            "# header\nterminal_app = \"kitty\" # my terminal\n\n[ui]\napps_per_page = 5\n";
+        let patched = apply_patch(
+            ConfigFormat::Toml,
+            content,
+            &json!({ "terminal_app": "foot", "ui": { "layout": "grid" } }),
+        )
+        .unwrap();
+        assert_eq!(
+            patched,
+            "# header\nterminal_app = \"foot\" # my terminal\n\n[ui]\napps_per_page = 5\nlayout = \"grid\"\n"
+        );
+    }
+
+    #[test]
+    fn apply_patch_creates_toml_sections() {
+        let patched = apply_patch(
+            ConfigFormat::Toml,
+            "version = 1\n",
+            &json!({ "keybindings": { "bindings": { "Ctrl+k": "prev" } } }),
+        )
+        .unwrap();
+        assert_eq!(
+            ConfigFormat::Toml.to_value(&patched).unwrap(),
+            json!({ "version": 1, "keybindings": { "bindings": { "Ctrl+k": "prev" } } })
+        );
+    }
+
+    #[test]
+    fn apply_patch_keeps_jsonc_comments() {
+        let content = "{\n  // terminal\n  \"terminal_app\": \"kitty\",\n  \"ui\": {\n    \"apps_per_page\": 5 // five\n  }\n}\n";
+        let patched = apply_patch(
+            ConfigFormat::Jsonc,
+            content,
+            &json!({ "terminal_app": "foot", "ui": { "layout": "grid" } }),
+        )
+        .unwrap();
+        assert_eq!(
+            patched,
+            "{\n  // terminal\n  \"terminal_app\": \"foot\",\n  \"ui\": {\n    \"apps_per_page\": 5, // five\n    \"layout\": \"grid\"\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn apply_patch_adds_missing_jsonc_objects() {
+        let patched = apply_patch(
+            ConfigFormat::Jsonc,
+            "{ /* } */ }",
+            &json!({ "ui": { "theme": "neon" } }),
+        )
+        .unwrap();
+        assert_eq!(
+            ConfigFormat::Jsonc.to_value(&patched).unwrap(),
+            json!({ "ui": { "theme": "neon" } })
+        );
+        assert!(patched.contains("/* } */"));
+    }
+
+    #[test]
+    fn insert_keys_appends_after_trailing_comments() {
+        let content = "{\n  \"version\": 3 // keep\n}\n";
+        let inserted = insert_keys(
+            ConfigFormat::Jsonc,
+            content,
+            object(json!({ "icon_theme": "", "lookup_dirs": { "extend": [] } })),
+        )
+        .unwrap();
+        assert_eq!(
+            inserted,
+            "{\n  \"version\": 3, // keep\n  \"icon_theme\": \"\",\n  \"lookup_dirs\": {\n    \"extend\": []\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn insert_keys_appends_toml_sections() {
+        let content = "# comment\nversion = 1\n\n[ui]\nanimations = false\n";
+        let inserted = insert_keys(
+            ConfigFormat::Toml,
+            content,
+            object(json!({ "icon_theme": "", "cache": { "format": "json" } })),
+        )
+        .unwrap();
+        assert_eq!(
+            inserted,
+            "# comment\nversion = 1\nicon_theme = \"\"\n\n[ui]\nanimations = false\n\n[cache]\nformat = \"json\"\n"
+        );
+    }
+
+    #[test]
+    fn diff_values_only_touches_changed_keys() {
+        let old = json!({ "a": 1, "ui": { "b": 2, "c": 3 } });
+        let new = json!({ "ui": { "b": 2, "c": 4 }, "d": [1] });
+        assert_eq!(
+            diff_values(&old, &new),
+            vec![
+                ConfigEdit::Remove(vec!["a".into()]),
+                ConfigEdit::Set(vec!["ui".into(), "c".into()], json!(4)),
+                ConfigEdit::Set(vec!["d".into()], json!([1])),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_jsonc_members_take_their_comma_and_line() {
+        let content = "{\n  \"a\": 1, // gone\n  \"b\": 2,\n  \"c\": 3 // gone too\n}\n";
+        let edits = [
+            ConfigEdit::Remove(vec!["a".into()]),
+            ConfigEdit::Remove(vec!["c".into()]),
+        ];
+        assert_eq!(
+            apply_edits(ConfigFormat::Jsonc, content, &edits).unwrap(),
+            "{\n  \"b\": 2\n}\n"
+        );
+    }
+
+    #[test]
+    fn removed_toml_keys_keep_the_rest() {
+        let content = "# top\napps_per_page = 5 # old\nterminal_app = \"foot\"\n";
+        let edits = [
+            ConfigEdit::Remove(vec!["apps_per_page".into()]),
+            ConfigEdit::Set(vec!["ui".into()], json!({ "apps_per_page": 5 })),
+        ];
+        assert_eq!(
+            apply_edits(ConfigFormat::Toml, content, &edits).unwrap(),
+            "# top\nterminal_app = \"foot\"\n\n[ui]\napps_per_page = 5\n"
+        );
+    }
+
+    #[test]
+    fn toml_values_becoming_sections_keep_their_comments() {
+        let content = "# header\nkde_icon_theme = \"Papirus\"\n# dirs\nlookup_dirs = [\"/a\"]\n";
+        let old = ConfigFormat::Toml.to_value(content).unwrap();
+        let new = json!({
+            "icon_theme": "Papirus",
+            "lookup_dirs": { "extend": [], "override": ["/a"] },
+            "version": 1
+        });
+        assert_eq!(
+            apply_edits(ConfigFormat::Toml, content, &diff_values(&old, &new)).unwrap(),
+            "# header\nicon_theme = \"Papirus\"\nversion = 1\n\n# dirs\n[lookup_dirs]\nextend = []\noverride = [\"/a\"]\n"
+        );
+    }
+
+    #[test]
+    fn jsonc_edits_skip_escaped_quotes_in_strings() {
+        let content = "{\n  \"placeholder\": \"say \\\"terminal_app\\\": // {\",\n  \"terminal_app\": \"kitty\"\n}\n";
+        let patched = apply_patch(
+            ConfigFormat::Jsonc,
+            content,
+            &json!({ "terminal_app": "foot", "placeholder": "a \"b\"" }),
+        )
+        .unwrap();
+        assert_eq!(
+            patched,
+            "{\n  \"placeholder\": \"a \\\"b\\\"\",\n  \"terminal_app\": \"foot\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn jsonc_edits_only_match_keys_of_the_edited_object() {
+        let content = "{\n  \"ui\": {\n    \"theme\": { \"layout\": [\"}\", { \"x\": 1 }] },\n    \"layout\": \"list\"\n  },\n  \"layout\": \"top\"\n}\n";
+        let patched = apply_patch(
+            ConfigFormat::Jsonc,
+            content,
+            &json!({ "ui": { "layout": "grid" } }),
+        )
+        .unwrap();
+        assert_eq!(
+            patched,
+            "{\n  \"ui\": {\n    \"theme\": { \"layout\": [\"}\", { \"x\": 1 }] },\n    \"layout\": \"grid\"\n  },\n  \"layout\": \"top\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn jsonc_edits_keep_trailing_commas_valid() {
+        let content = "{\n  \"a\": 1,\n  \"b\": {\n    \"c\": 2,\n  },\n}\n";
+        let edits = [
+            ConfigEdit::Set(vec!["b".into(), "d".into()], json!(3)),
+            ConfigEdit::Remove(vec!["a".into()]),
+        ];
+        assert_eq!(
+            apply_edits(ConfigFormat::Jsonc, content, &edits).unwrap(),
+            "{\n  \"b\": {\n    \"c\": 2,\n    \"d\": 3\n  },\n}\n"
+        );
+    }
+}
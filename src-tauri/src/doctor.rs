@@ -0,0 +1,236 @@
+use crate::app_index;
+use crate::commands::{self, IconResolution, SkipReason};
+use crate::config::{self, EffectiveConfig};
+use crate::icon_theme;
+use crate::icons;
+use crate::layers::ConfigSource;
+use crate::paths;
+use rayon::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+
+/// Why applications are or aren't listed, for `cyberdeck doctor`.
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "../../src/types/DoctorReport.ts")]
+pub struct DoctorReport {
+    pub version: String,
+    pub config: EffectiveConfig,
+    pub directories: Vec<DirectoryReport>,
+    pub lookup_dirs: Vec<LookupDirReport>,
+    /// the configured icon theme, or the one detected from the desktop settings
+    pub icon_theme: String,
+    pub scale_factor: u32,
+    /// every desktop file below the lookup directories, in the order they are listed
+    pub desktop_files: Vec<DesktopFileReport>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "../../src/types/DirectoryReport.ts")]
+pub struct DirectoryReport {
+    /// e.g. `cache`
+    pub name: String,
+    /// `None` when neither its XDG variable nor HOME is set
+    pub path: Option<PathBuf>,
+    pub exists: bool,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "../../src/types/LookupDirReport.ts")]
+pub struct LookupDirReport {
+    pub path: String,
+    pub exists: bool,
+    pub desktop_files: usize,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "../../src/types/DesktopFileReport.ts")]
+pub struct DesktopFileReport {
+    pub path: PathBuf,
+    /// what shadowing compares, e.g. `kde-konsole.desktop`
+    pub desktop_file_id: String,
+    pub status: DesktopFileStatus,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/DesktopFileStatus.ts")]
+pub enum DesktopFileStatus {
+    Listed { name: String, icon: IconResolution },
+    Skipped { reason: SkipReason },
+}
+
+/// Evaluates every desktop file again, without the index,
+/// so the report shows the files as they are now.
+pub fn diagnose() -> Result<DoctorReport, String> {
+    let effective = config::load_effective_config()?;
+    let config = &effective.config;
+    let lookup_dirs = config.lookup_dirs.resolve();
+
+    let desktop_files: Vec<DesktopFileReport> = app_index::find_desktop_files(&lookup_dirs)
+        .into_par_iter()
+        .map(|(path, desktop_file_id, shadowed_by)| {
+            let status = match shadowed_by {
+                Some(by) => DesktopFileStatus::Skipped {
+                    reason: SkipReason::Shadowed { by },
+                },
+                None => match commands::parse_application_from_file(
+                    path.to_string_lossy().into_owned(),
+                    &desktop_file_id,
+                    &config.desktop_environment,
+                    &config.terminal_app,
+                    &config.icon_theme,
+                    config.ui.icon_size,
+                ) {
+                    Ok((application, _)) if !application.is_installed() => {
+                        DesktopFileStatus::Skipped {
+                            reason: SkipReason::TryExec {
+                                program: application.try_exec.unwrap_or_default(),
+                            },
+                        }
+                    }
+                    Ok((application, icon)) => DesktopFileStatus::Listed {
+                        name: application.name,
+                        icon,
+                    },
+                    Err(reason) => DesktopFileStatus::Skipped { reason },
+                },
+            };
+            DesktopFileReport {
+                path,
+                desktop_file_id,
+                status,
+            }
+        })
+        .collect();
+
+    let lookup_dirs = lookup_dirs
+        .into_iter()
+        .map(|dir| LookupDirReport {
+            exists: Path::new(&dir).is_dir(),
+            desktop_files: desktop_files
+                .iter()
+                .filter(|file| file.path.starts_with(&dir))
+                .count(),
+            path: dir,
+        })
+        .collect();
+    let directories = paths::directories()
+        .into_iter()
+        .map(|(name, path)| DirectoryReport {
+            name: name.to_string(),
+            exists: path.as_ref().is_some_and(|path| path.is_dir()),
+            path,
+        })
+        .collect();
+
+    Ok(DoctorReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        icon_theme: icon_theme::resolve_theme_name(&config.icon_theme),
+        scale_factor: icons::scale_factor(),
+        directories,
+        lookup_dirs,
+        desktop_files,
+        config: effective,
+    })
+}
+
+/// `cyberdeck doctor` for the frontend.
+#[tauri::command]
+pub async fn get_diagnostics() -> Result<DoctorReport, String> {
+    // every desktop file is parsed, it runs off the async runtime like a scan
+    tauri::async_runtime::spawn_blocking(diagnose)
+        .await
+        .map_err(|e| format!("Diagnostics failed: {e}"))?
+}
+
+// dotted key path -> value, like the keys of `EffectiveConfig::sources`
+fn flatten_config(path: &str, value: &Value, flattened: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let key_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                flatten_config(&key_path, value, flattened);
+            }
+        }
+        _ => flattened.push((path.to_string(), value.to_string())),
+    }
+}
+
+impl std::fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Cyberdeck {}", self.version)?;
+
+        writeln!(f, "\nDirectories:")?;
+        for directory in &self.directories {
+            match &directory.path {
+                Some(path) if directory.exists => {
+                    writeln!(f, "  {:<8} {}", directory.name, path.display())?
+                }
+                Some(path) => writeln!(f, "  {:<8} {} (missing)", directory.name, path.display())?,
+                None => writeln!(f, "  {:<8} (not available)", directory.name)?,
+            }
+        }
+
+        writeln!(f, "\nConfig:")?;
+        let mut flattened = vec![];
+        if let Ok(value) = serde_json::to_value(&self.config.config) {
+            flatten_config("", &value, &mut flattened);
+        }
+        for (key, value) in flattened {
+            let source = self
+                .config
+                .sources
+                .get(&key)
+                .unwrap_or(&ConfigSource::Default);
+            writeln!(f, "  {key} = {value} ({source})")?;
+        }
+
+        writeln!(f, "\nIcons:")?;
+        writeln!(f, "  theme    {}", self.icon_theme)?;
+        writeln!(f, "  scale    {}x", self.scale_factor)?;
+
+        writeln!(f, "\nLookup directories:")?;
+        for lookup_dir in &self.lookup_dirs {
+            if lookup_dir.exists {
+                writeln!(
+                    f,
+                    "  {} ({} desktop files)",
+                    lookup_dir.path, lookup_dir.desktop_files
+                )?;
+            } else {
+                writeln!(f, "  {} (missing)", lookup_dir.path)?;
+            }
+        }
+
+        let listed = self
+            .desktop_files
+            .iter()
+            .filter(|file| matches!(file.status, DesktopFileStatus::Listed { .. }))
+            .count();
+        writeln!(
+            f,
+            "\nDesktop files: {} considered, {listed} listed, {} skipped",
+            self.desktop_files.len(),
+            self.desktop_files.len() - listed
+        )?;
+        for file in &self.desktop_files {
+            match &file.status {
+                DesktopFileStatus::Listed { name, icon } => {
+                    writeln!(f, "  listed   {} ({name})", file.path.display())?;
+                    writeln!(f, "           icon {icon}")?;
+                }
+                DesktopFileStatus::Skipped { reason } => {
+                    writeln!(f, "  skipped  {}", file.path.display())?;
+                    writeln!(f, "           {reason}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use crate::app_index::APP_INDEX;
+use crate::config::APP_CONFIG;
+use crate::config_file;
+use crate::paths;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILE_NAME: &str = "history.json";
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How often and how recently applications were launched.
+#[derive(Serialize, Deserialize, Default)]
+pub struct LaunchHistory {
+    /// application id -> its launches
+    launches: BTreeMap<String, Launches>,
+}
+
+/// The launches of one application.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Launches {
+    pub count: u32,
+    /// unix time in seconds
+    pub last_launch: u64,
+}
+
+impl Launches {
+    /// The launch count weighted by how long ago the last launch was,
+    /// like the frecency of Firefox' address bar.
+    pub fn frecency(&self, now: u64) -> f32 {
+        let age_in_days = now.saturating_sub(self.last_launch) / SECONDS_PER_DAY;
+        let weight = match age_in_days {
+            0..=4 => 100.0,
+            5..=14 => 70.0,
+            15..=31 => 50.0,
+            32..=90 => 30.0,
+            _ => 10.0,
+        };
+        self.count as f32 * weight
+    }
+}
+
+/// `$XDG_STATE_HOME/cyberdeck/history.json`
+pub fn get_history_path() -> Option<PathBuf> {
+    paths::state_dir().map(|dir| dir.join(HISTORY_FILE_NAME))
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+impl LaunchHistory {
+    /// An empty history when there is none yet or it can't be read.
+    pub fn load() -> Self {
+        let Some(history_path) = get_history_path() else {
+            warn!("Neither XDG_STATE_HOME nor HOME is set, starting without a launch history");
+            return Self::default();
+        };
+        let data = match fs::read_to_string(&history_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("Failed to read the launch history: {e}");
+                return Self::default();
+            }
+        };
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("Failed to parse the launch history, starting a new one: {e}");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let history_path = get_history_path().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Neither XDG_STATE_HOME nor HOME is set",
+            )
+        })?;
+        if let Some(parent) = history_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string(self)
+            .map_err(|e| std::io::Error::other(format!("Serialization error: {e}")))?;
+        config_file::write_atomically(&history_path, json)
+    }
+
+    /// Adds a launch and returns the launches of the application so far.
+    pub fn record(&mut self, id: &str, now: u64) -> Launches {
+        let launches = self.launches.entry(id.to_string()).or_insert(Launches {
+            count: 0,
+            last_launch: now,
+        });
+        launches.count = launches.count.saturating_add(1);
+        launches.last_launch = now;
+        *launches
+    }
+
+    /// `None` for never launched applications.
+    pub fn launches(&self, id: &str) -> Option<Launches> {
+        self.launches.get(id).copied()
+    }
+}
+
+/// Records a launch of the application in the history and in the index,
+/// so it is sorted by frecency the next time the launcher opens without
+/// reading the history.
+pub fn record_launch(id: &str) {
+    let mut history = LaunchHistory::load();
+    let launches = history.record(id, now());
+    if let Err(e) = history.save() {
+        error!("Failed to save the launch history: {e}");
+        return;
+    }
+
+    let format = match APP_CONFIG.lock() {
+        Ok(config) => config.cache.format,
+        Err(e) => {
+            error!("Failed to lock config: {e}");
+            return;
+        }
+    };
+    let Ok(mut index) = APP_INDEX.lock() else {
+        error!("Failed to lock the application index");
+        return;
+    };
+    // without an index, the next scan copies the launches from the history
+    if index.is_empty() {
+        return;
+    }
+    index.record_launch(id, launches);
+    if let Err(e) = index.save(format) {
+        error!("Error occurred when writing the application index: {e}");
+    }
+}
@@ -0,0 +1,183 @@
+use crate::config_file;
+use crate::paths;
+use crate::stable_hash::StableHasher;
+#[cfg(debug_assertions)]
+use log::debug;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use resvg::usvg::fontdb;
+use resvg::{tiny_skia, usvg};
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+const RASTERIZED_EXTENSIONS: [&str; 3] = ["svg", "svgz", "png"];
+
+// loaded once, SVGs with text (like the placeholders) render nothing without fonts
+static FONT_DATABASE: Lazy<Arc<fontdb::Database>> = Lazy::new(|| {
+    let mut database = fontdb::Database::new();
+    database.load_system_fonts();
+    // fontconfig's preferred sans-serif font often isn't installed, text is
+    // dropped then instead of falling back to another font
+    let has_sans_serif = database
+        .query(&fontdb::Query {
+            families: &[fontdb::Family::SansSerif],
+            ..fontdb::Query::default()
+        })
+        .is_some();
+    if !has_sans_serif {
+        let families: Vec<&String> = database
+            .faces()
+            .filter_map(|face| face.families.first())
+            .map(|(family, _)| family)
+            .collect();
+        let fallback = families
+            .iter()
+            .find(|family| family.contains("Sans") && !family.contains("Mono"))
+            .or(families.first())
+            .map(|family| family.to_string());
+        if let Some(fallback) = fallback {
+            database.set_sans_serif_family(fallback);
+        }
+    }
+    Arc::new(database)
+});
+
+/// `$XDG_CACHE_HOME/cyberdeck/icons`
+pub fn get_icon_cache_dir() -> Option<PathBuf> {
+    paths::cache_dir().map(|dir| dir.join("icons"))
+}
+
+/// Where the rasterised copy of `source` at `size` physical pixels is stored.
+/// The name changes with the source's path, modification time and size,
+/// so an updated icon is rasterised again.
+pub fn cache_path(source: &Path, size: u32) -> Option<PathBuf> {
+    let metadata = std::fs::metadata(source).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    let mut hasher = StableHasher::new();
+    hasher.write_field(source.as_os_str().as_bytes());
+    hasher.write_field(modified.as_nanos().to_le_bytes());
+    hasher.write_field(metadata.len().to_le_bytes());
+    let key = hasher.finish();
+
+    get_icon_cache_dir().map(|dir| dir.join(format!("{key:016x}-{size}.png")))
+}
+
+// the part of a cached icon's name that identifies its source, shared by all its sizes
+fn source_key(cached: &Path) -> Option<&str> {
+    let name = cached.file_name()?.to_str()?.strip_suffix(".png")?;
+    name.rsplit_once('-').map(|(key, _)| key)
+}
+
+/// The cached PNG of `source`, rasterised now when missing.
+/// `None` when it can't be rasterised, the source should be served as is then.
+pub fn get_cached_icon(source: &Path, size: u32) -> Option<PathBuf> {
+    // XPM icons are served as they are
+    let extension = source.extension()?.to_str()?.to_lowercase();
+    if !RASTERIZED_EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+    let cached_path = cache_path(source, size)?;
+    if cached_path.is_file() {
+        return Some(cached_path);
+    }
+
+    let png = match rasterize(source, &extension, size) {
+        Ok(png) => png,
+        Err(e) => {
+            warn!("Failed to rasterise icon {source:?}: {e}");
+            return None;
+        }
+    };
+    if let Some(parent) = cached_path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            error!("Failed to create icon cache directory {parent:?}: {e}");
+            return None;
+        }
+    }
+    if let Err(e) = config_file::write_atomically(&cached_path, &png) {
+        error!("Failed to write cached icon {cached_path:?}: {e}");
+        return None;
+    }
+    #[cfg(debug_assertions)]
+    debug!("Cached icon {source:?} at {size}px as {cached_path:?}");
+    Some(cached_path)
+}
+
+// fits the icon into a `size` x `size` square, keeping its aspect ratio
+fn rasterize(source: &Path, extension: &str, size: u32) -> Result<Vec<u8>, String> {
+    let data = std::fs::read(source).map_err(|e| e.to_string())?;
+    match extension {
+        "svg" | "svgz" => {
+            let options = usvg::Options {
+                // for images referenced by the SVG
+                resources_dir: source.parent().map(Path::to_path_buf),
+                fontdb: FONT_DATABASE.clone(),
+                ..usvg::Options::default()
+            };
+            let tree = usvg::Tree::from_data(&data, &options).map_err(|e| e.to_string())?;
+            let tree_size = tree.size();
+            let scale = size as f32 / tree_size.width().max(tree_size.height());
+            let width = (tree_size.width() * scale).round().max(1.0) as u32;
+            let height = (tree_size.height() * scale).round().max(1.0) as u32;
+            let mut pixmap = tiny_skia::Pixmap::new(width, height)
+                .ok_or_else(|| format!("invalid size {width}x{height}"))?;
+            resvg::render(
+                &tree,
+                tiny_skia::Transform::from_scale(scale, scale),
+                &mut pixmap.as_mut(),
+            );
+            pixmap.encode_png().map_err(|e| e.to_string())
+        }
+        "png" => {
+            let image = image::load_from_memory(&data).map_err(|e| e.to_string())?;
+            // small icons are stored as they are, scaling them up only blurs them
+            let image = if image.width() > size || image.height() > size {
+                image.resize(size, size, image::imageops::FilterType::Lanczos3)
+            } else {
+                image
+            };
+            let mut png = vec![];
+            image
+                .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+                .map_err(|e| e.to_string())?;
+            Ok(png)
+        }
+        _ => Err(format!("unsupported icon format '{extension}'")),
+    }
+}
+
+/// Removes cached icons and placeholders of sources that no file in `keep` was
+/// made from, e.g. of uninstalled applications. Other sizes of a kept icon stay,
+/// so moving the window between screens doesn't rasterise them again.
+pub fn collect_garbage(keep: &HashSet<PathBuf>) {
+    let Some(cache_dir) = get_icon_cache_dir() else {
+        return;
+    };
+    let kept_sources: HashSet<&str> = keep.iter().filter_map(|path| source_key(path)).collect();
+
+    let mut removed = 0;
+    for entry in WalkDir::new(&cache_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+    {
+        let path = entry.path();
+        let is_kept =
+            keep.contains(path) || source_key(path).is_some_and(|key| kept_sources.contains(key));
+        if is_kept || !entry.file_type().is_file() {
+            continue;
+        }
+        match std::fs::remove_file(path) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Failed to remove cached icon {path:?}: {e}"),
+        }
+    }
+    if removed > 0 {
+        info!("Removed {removed} unused icons from {cache_dir:?}");
+    }
+}
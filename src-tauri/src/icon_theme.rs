@@ -0,0 +1,460 @@
+use crate::paths;
+#[cfg(debug_assertions)]
+use log::{debug, error};
+use log::{info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+// in order of preference when a directory has several
+const ICON_EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+
+const FALLBACK_THEME: &str = "hicolor";
+
+/// `$HOME/.icons`, `$XDG_DATA_HOME/icons`, `$XDG_DATA_DIRS/icons` and `/usr/share/pixmaps`,
+/// from the most to the least important.
+fn get_icon_base_dirs() -> &'static [PathBuf] {
+    static BASE_DIRS: Lazy<Vec<PathBuf>> = Lazy::new(|| {
+        let home = paths::home_dir();
+        let data_home = paths::data_home();
+        let data_dirs = std::env::var("XDG_DATA_DIRS")
+            .ok()
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| String::from("/usr/local/share:/usr/share"));
+
+        let mut base_dirs = vec![];
+        base_dirs.extend(home.map(|home| home.join(".icons")));
+        base_dirs.extend(data_home.map(|dir| dir.join("icons")));
+        base_dirs.extend(
+            data_dirs
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(|dir| PathBuf::from(dir).join("icons")),
+        );
+        base_dirs.push(PathBuf::from("/usr/share/pixmaps"));
+        base_dirs
+    });
+    &BASE_DIRS
+}
+
+/// Where the active icon theme was found, e.g. "gsettings".
+pub type IconThemeSource = &'static str;
+
+type IconThemeDetector = fn() -> Option<(String, IconThemeSource)>;
+
+/// The configured icon theme, or the one detected from the desktop settings.
+/// Detection runs once, an empty `configured` theme means "detect".
+pub fn resolve_theme_name(configured: &str) -> String {
+    if !configured.is_empty() {
+        return configured.to_string();
+    }
+    static DETECTED: OnceCell<String> = OnceCell::new();
+    DETECTED
+        .get_or_init(|| match detect_icon_theme() {
+            Some((theme, source)) => {
+                info!("Using icon theme '{theme}' from {source}");
+                theme
+            }
+            None => {
+                info!("No icon theme detected, using '{FALLBACK_THEME}'");
+                FALLBACK_THEME.to_string()
+            }
+        })
+        .clone()
+}
+
+/// Reads the icon theme from the settings of the running desktop.
+/// KDE settings are tried first on Plasma and gsettings first on GNOME and the desktops
+/// built on it. Everywhere else gtk-3.0/settings.ini comes first: gsettings reports
+/// its default theme there even though the desktop never set one.
+pub fn detect_icon_theme() -> Option<(String, IconThemeSource)> {
+    let current_desktops = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let is_desktop = |names: &[&str]| {
+        current_desktops
+            .split(':')
+            .any(|desktop| names.contains(&desktop))
+    };
+    let kde6: IconThemeDetector = || read_kde_icon_theme("kreadconfig6");
+    let kde5: IconThemeDetector = || read_kde_icon_theme("kreadconfig5");
+    let gsettings: IconThemeDetector = read_gsettings_icon_theme;
+    let settings_ini: IconThemeDetector = read_gtk_settings_icon_theme;
+
+    let detectors = if is_desktop(&["KDE"]) {
+        [kde6, kde5, settings_ini, gsettings]
+    } else if is_desktop(&GSETTINGS_DESKTOPS) {
+        [gsettings, settings_ini, kde6, kde5]
+    } else {
+        [settings_ini, gsettings, kde6, kde5]
+    };
+    detectors.iter().find_map(|detect| detect())
+}
+
+// `XDG_CURRENT_DESKTOP` names of desktops keeping their icon theme in
+// `org.gnome.desktop.interface`
+const GSETTINGS_DESKTOPS: [&str; 5] = ["GNOME", "Unity", "Budgie", "Pantheon", "X-Cinnamon"];
+
+fn read_command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = match Command::new(program).args(args).output() {
+        Ok(output) => output,
+        Err(_e) => {
+            #[cfg(debug_assertions)]
+            debug!("Failed to execute {program}: {_e}");
+            return None;
+        }
+    };
+    if !output.status.success() {
+        #[cfg(debug_assertions)]
+        error!(
+            "{program} failed: {stderr}",
+            stderr = String::from_utf8_lossy(&output.stderr)
+        );
+        return None;
+    }
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn read_gsettings_icon_theme() -> Option<(String, IconThemeSource)> {
+    let value = read_command_output(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "icon-theme"],
+    )?;
+    // printed as a GVariant string, e.g. 'Adwaita'
+    let theme = value.trim_matches('\'').to_string();
+    (!theme.is_empty()).then_some((theme, "gsettings"))
+}
+
+fn read_gtk_settings_icon_theme() -> Option<(String, IconThemeSource)> {
+    let config_home = paths::config_home()?;
+    let content = std::fs::read_to_string(config_home.join("gtk-3.0/settings.ini")).ok()?;
+    let theme = parse_ini(&content)
+        .get("Settings")?
+        .get("gtk-icon-theme-name")?
+        .trim_matches('"')
+        .to_string();
+    (!theme.is_empty()).then_some((theme, "gtk-3.0/settings.ini"))
+}
+
+fn read_kde_icon_theme(program: &'static str) -> Option<(String, IconThemeSource)> {
+    let theme = read_command_output(
+        program,
+        &["--file", "kdeglobals", "--group", "Icons", "--key", "Theme"],
+    )?;
+    Some((theme, program))
+}
+
+type IniSections = HashMap<String, HashMap<String, String>>;
+
+// enough of the desktop entry syntax for index.theme and settings.ini
+fn parse_ini(content: &str) -> IniSections {
+    let mut sections = IniSections::new();
+    let mut current_section = None;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current_section = Some(section.to_string());
+            continue;
+        }
+        if let (Some(section), Some((key, value))) = (&current_section, line.split_once('=')) {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirectoryType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// A subdirectory of a theme, like `48x48/apps`, as described in its index.theme.
+#[derive(Debug)]
+struct ThemeDirectory {
+    size: u32,
+    scale: u32,
+    kind: DirectoryType,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+impl ThemeDirectory {
+    fn parse(keys: &HashMap<String, String>) -> Option<ThemeDirectory> {
+        let number = |key: &str| keys.get(key).and_then(|value| value.parse::<u32>().ok());
+        let size = number("Size")?;
+        Some(ThemeDirectory {
+            size,
+            scale: number("Scale").unwrap_or(1),
+            kind: match keys.get("Type").map(String::as_str) {
+                Some("Fixed") => DirectoryType::Fixed,
+                Some("Scalable") => DirectoryType::Scalable,
+                _ => DirectoryType::Threshold,
+            },
+            min_size: number("MinSize").unwrap_or(size),
+            max_size: number("MaxSize").unwrap_or(size),
+            threshold: number("Threshold").unwrap_or(2),
+        })
+    }
+
+    fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.kind {
+            DirectoryType::Fixed => self.size == size,
+            DirectoryType::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirectoryType::Threshold => (self.size.saturating_sub(self.threshold)
+                ..=self.size + self.threshold)
+                .contains(&size),
+        }
+    }
+
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size * scale;
+        let (min, max) = match self.kind {
+            DirectoryType::Fixed => (self.size, self.size),
+            DirectoryType::Scalable => (self.min_size, self.max_size),
+            DirectoryType::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+        let (min, max) = (min * self.scale, max * self.scale);
+        if wanted < min {
+            min - wanted
+        } else {
+            wanted.saturating_sub(max)
+        }
+    }
+}
+
+/// A parsed theme, see the freedesktop Icon Theme Specification.
+/// Every icon file it contains is listed up front, so lookups don't touch the disk.
+#[derive(Debug)]
+struct IconTheme {
+    parents: Vec<String>,
+    directories: Vec<ThemeDirectory>,
+    /// icon name -> (index into `directories`, file), in directory and extension order
+    icons: HashMap<String, Vec<(usize, PathBuf)>>,
+}
+
+impl IconTheme {
+    fn load(name: &str) -> Option<IconTheme> {
+        let theme_dirs: Vec<PathBuf> = get_icon_base_dirs()
+            .iter()
+            .map(|base_dir| base_dir.join(name))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        // the first index.theme found describes the theme, other base dirs may add files
+        let index_path = theme_dirs
+            .iter()
+            .map(|dir| dir.join("index.theme"))
+            .find(|path| path.is_file())?;
+        let index = parse_ini(&std::fs::read_to_string(&index_path).ok()?);
+        let theme_section = index.get("Icon Theme")?;
+        let list = |key: &str| -> Vec<String> {
+            theme_section
+                .get(key)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut directory_names = list("Directories");
+        for scaled in list("ScaledDirectories") {
+            if !directory_names.contains(&scaled) {
+                directory_names.push(scaled);
+            }
+        }
+
+        let mut directories = vec![];
+        let mut icons: HashMap<String, Vec<(usize, PathBuf)>> = HashMap::new();
+        for directory_name in directory_names {
+            let Some(directory) = index.get(&directory_name).and_then(ThemeDirectory::parse) else {
+                continue;
+            };
+            let directory_index = directories.len();
+            directories.push(directory);
+
+            let mut files: Vec<(String, usize, PathBuf)> = theme_dirs
+                .iter()
+                .filter_map(|theme_dir| std::fs::read_dir(theme_dir.join(&directory_name)).ok())
+                .flatten()
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    let extension = path.extension()?.to_str()?;
+                    let priority = ICON_EXTENSIONS.iter().position(|ext| *ext == extension)?;
+                    let icon_name = path.file_stem()?.to_str()?.to_string();
+                    Some((icon_name, priority, path))
+                })
+                .collect();
+            files.sort_by_key(|(_, priority, _)| *priority);
+            for (icon_name, _, path) in files {
+                icons
+                    .entry(icon_name)
+                    .or_default()
+                    .push((directory_index, path));
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        debug!(
+            "Loaded icon theme '{name}' from {index_path:?}: {count} icons",
+            count = icons.len()
+        );
+        Some(IconTheme {
+            parents: list("Inherits"),
+            directories,
+            icons,
+        })
+    }
+
+    /// A scalable SVG first, as it stays sharp at every size and scale,
+    /// then an exact size match, otherwise the file with the closest size.
+    fn lookup(&self, icon_name: &str, size: u32, scale: u32) -> Option<PathBuf> {
+        let candidates = self.icons.get(icon_name)?;
+        if let Some((_, path)) = candidates.iter().find(|(directory, path)| {
+            self.directories[*directory].kind == DirectoryType::Scalable
+                && path.extension().is_some_and(|extension| extension == "svg")
+        }) {
+            return Some(path.clone());
+        }
+        if let Some((_, path)) = candidates
+            .iter()
+            .find(|(directory, _)| self.directories[*directory].matches_size(size, scale))
+        {
+            return Some(path.clone());
+        }
+        candidates
+            .iter()
+            .min_by_key(|(directory, _)| self.directories[*directory].size_distance(size, scale))
+            .map(|(_, path)| path.clone())
+    }
+}
+
+// theme name -> parsed theme, `None` when it isn't installed
+static THEMES: Lazy<Mutex<HashMap<String, Option<Arc<IconTheme>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn get_theme(name: &str) -> Option<Arc<IconTheme>> {
+    let mut themes = THEMES.lock().ok()?;
+    themes
+        .entry(name.to_string())
+        .or_insert_with(|| IconTheme::load(name).map(Arc::new))
+        .clone()
+}
+
+/// Drops the parsed themes, they are listed again on the next lookup.
+pub fn forget_themes() {
+    match THEMES.lock() {
+        Ok(mut themes) => themes.clear(),
+        Err(e) => warn!("Failed to lock icon themes: {e}"),
+    }
+}
+
+/// Finds an icon by name in `theme_name`, the themes it inherits from and "hicolor",
+/// then directly in the base directories like `/usr/share/pixmaps`.
+pub fn find_icon(icon_name: &str, size: u32, scale: u32, theme_name: &str) -> Option<PathBuf> {
+    // some desktop entries name the file instead of the icon
+    let icon_name = ICON_EXTENSIONS
+        .iter()
+        .find_map(|extension| icon_name.strip_suffix(&format!(".{extension}")))
+        .unwrap_or(icon_name);
+
+    let mut visited = HashSet::new();
+    find_in_theme(icon_name, size, scale, theme_name, &mut visited)
+        .or_else(|| find_in_theme(icon_name, size, scale, FALLBACK_THEME, &mut visited))
+        .or_else(|| find_fallback_icon(icon_name))
+}
+
+fn find_in_theme(
+    icon_name: &str,
+    size: u32,
+    scale: u32,
+    theme_name: &str,
+    visited: &mut HashSet<String>,
+) -> Option<PathBuf> {
+    // themes may inherit from each other in a loop
+    if !visited.insert(theme_name.to_string()) {
+        return None;
+    }
+    let theme = get_theme(theme_name)?;
+    theme.lookup(icon_name, size, scale).or_else(|| {
+        theme
+            .parents
+            .iter()
+            .find_map(|parent| find_in_theme(icon_name, size, scale, parent, visited))
+    })
+}
+
+fn find_fallback_icon(icon_name: &str) -> Option<PathBuf> {
+    get_icon_base_dirs().iter().find_map(|base_dir| {
+        ICON_EXTENSIONS
+            .iter()
+            .map(|extension| base_dir.join(format!("{icon_name}.{extension}")))
+            .find(|path| path.is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // added to the cache directly, so no theme has to be installed
+    fn add_theme(name: &str, parents: &[&str], icon_names: &[&str]) {
+        let directory =
+            ThemeDirectory::parse(&HashMap::from([(String::from("Size"), String::from("48"))]))
+                .unwrap();
+        let icons = icon_names
+            .iter()
+            .map(|icon_name| {
+                let path = PathBuf::from(format!("/icons/{name}/48x48/{icon_name}.png"));
+                (icon_name.to_string(), vec![(0, path)])
+            })
+            .collect();
+        let theme = IconTheme {
+            parents: parents.iter().map(|parent| parent.to_string()).collect(),
+            directories: vec![directory],
+            icons,
+        };
+        THEMES
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Some(Arc::new(theme)));
+    }
+
+    #[test]
+    fn inherits_cycles_end_the_lookup() {
+        add_theme("test-cycle-a", &["test-cycle-b"], &[]);
+        add_theme("test-cycle-b", &["test-cycle-a", "test-cycle-c"], &[]);
+        add_theme("test-cycle-c", &["test-cycle-c"], &["firefox"]);
+
+        let mut visited = HashSet::new();
+        assert_eq!(
+            find_in_theme("firefox", 48, 1, "test-cycle-a", &mut visited),
+            Some(PathBuf::from("/icons/test-cycle-c/48x48/firefox.png"))
+        );
+        let mut visited = HashSet::new();
+        assert_eq!(
+            find_in_theme("missing", 48, 1, "test-cycle-a", &mut visited),
+            None
+        );
+        assert_eq!(visited.len(), 3);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::commands::Application;
+use crate::config::APP_CONFIG;
+use crate::icon_cache;
+use crate::icon_theme;
+use crate::placeholder;
+use crate::protocol::{percent_decode, text_response};
+#[cfg(debug_assertions)]
+use log::debug;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::Emitter;
+
+/// Serves application icons by id, e.g. `cyberdeck-icon://localhost/firefox?size=48`.
+pub const ICON_SCHEME: &str = "cyberdeck-icon";
+
+/// Used when a request doesn't ask for a size.
+pub const DEFAULT_ICON_SIZE: u16 = 48;
+const MAX_ICON_SIZE: u16 = 1024;
+const MAX_ICON_SCALE: u32 = 8;
+
+// scale factor of the launcher window, 2 on most HiDPI screens
+static SCALE_FACTOR: AtomicU32 = AtomicU32::new(1);
+
+/// Set from the GTK window, icons are looked up for `size * scale factor` pixels.
+pub fn set_scale_factor(scale_factor: i32) {
+    SCALE_FACTOR.store(scale_factor.max(1) as u32, Ordering::Relaxed);
+}
+
+/// Called when the window moved to a screen with another scale factor,
+/// so the frontend requests its icons again.
+pub fn on_scale_factor_changed(app_handle: &tauri::AppHandle, scale_factor: i32) {
+    set_scale_factor(scale_factor);
+    info!("Window scale factor changed to {scale_factor}");
+    if let Err(e) = app_handle.emit("icon-scale-changed", self::scale_factor()) {
+        error!("Failed to notify the frontend about the scale change: {e}");
+    }
+}
+
+pub fn scale_factor() -> u32 {
+    SCALE_FACTOR.load(Ordering::Relaxed)
+}
+
+/// The scale factor the frontend adds to icon URLs until `icon-scale-changed` is emitted.
+#[tauri::command]
+pub fn get_icon_scale() -> u32 {
+    scale_factor()
+}
+
+// icons rarely change, but the URL stays the same when they do
+const CACHE_CONTROL: &str = "public, max-age=86400";
+
+// id (the `Icon` key of a desktop entry) of the known applications -> the icon file
+// the scan prepared and its size in physical pixels, nothing else is served
+static KNOWN_ICONS: Lazy<Mutex<HashMap<String, (PathBuf, u32)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// (id, icon theme, size, scale) -> icon file, `None` when the lookup found nothing
+type ResolvedIcons = HashMap<(String, String, u16, u32), Option<PathBuf>>;
+static RESOLVED_ICONS: Lazy<Mutex<ResolvedIcons>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets every icon lookup and parsed icon theme, so icons and themes installed
+/// since are found. Run before each scan and when the configured icon theme changes.
+pub fn forget_resolved_icons() {
+    match RESOLVED_ICONS.lock() {
+        Ok(mut resolved_icons) => resolved_icons.clear(),
+        Err(e) => warn!("Failed to lock resolved icons: {e}"),
+    }
+    icon_theme::forget_themes();
+}
+
+/// Allows the protocol to serve the icons of these applications, from their
+/// `icon_path` when requested at `size` physical pixels.
+pub fn register_icons(applications: &[Application], size: u32) {
+    match KNOWN_ICONS.lock() {
+        Ok(mut known_icons) => known_icons.extend(
+            applications
+                .iter()
+                .filter(|app| !app.icon.is_empty())
+                .map(|app| (app.icon.clone(), (PathBuf::from(&app.icon_path), size))),
+        ),
+        Err(e) => warn!("Failed to lock known icons: {e}"),
+    }
+}
+
+/// Finds the file of an icon name in `icon_theme` (detected when empty),
+/// or checks an absolute path or generated placeholder. `size` is in logical pixels.
+pub fn resolve_icon(id: &str, size: u16, scale: u32, icon_theme: &str) -> Option<PathBuf> {
+    let icon_theme = icon_theme::resolve_theme_name(icon_theme);
+    let key = (id.to_string(), icon_theme.clone(), size, scale);
+    if let Some(resolved) = RESOLVED_ICONS.lock().ok()?.get(&key) {
+        return resolved.clone();
+    }
+
+    let resolved = if let Some(placeholder) = placeholder::placeholder_path(id) {
+        Some(placeholder).filter(|path| path.is_file())
+    } else if Path::new(id).is_absolute() {
+        Some(PathBuf::from(id)).filter(|path| path.is_file())
+    } else {
+        icon_theme::find_icon(id, size.into(), scale, &icon_theme)
+    };
+    #[cfg(debug_assertions)]
+    debug!("Resolved icon '{id}' at {size}px@{scale}x to {resolved:?}");
+
+    if let Ok(mut resolved_icons) = RESOLVED_ICONS.lock() {
+        resolved_icons.insert(key, resolved.clone());
+    }
+    resolved
+}
+
+/// The rasterised copy of an icon in the icon cache, or its file when it can't be
+/// rasterised. `size` is in logical pixels.
+pub fn prepare_icon(id: &str, size: u16, scale: u32, icon_theme: &str) -> Option<PathBuf> {
+    let source = resolve_icon(id, size, scale, icon_theme)?;
+    icon_cache::get_cached_icon(&source, u32::from(size) * scale).or(Some(source))
+}
+
+/// Drops cached icons the given applications no longer use, in any size.
+pub fn clean_icon_cache<'a>(applications: impl IntoIterator<Item = &'a Application>) {
+    let mut used_icons = HashSet::new();
+    for app in applications.into_iter().filter(|app| !app.icon.is_empty()) {
+        used_icons.insert(PathBuf::from(&app.icon_path));
+        // placeholders live in the cache as well
+        used_icons.extend(placeholder::placeholder_path(&app.icon));
+    }
+    icon_cache::collect_garbage(&used_icons);
+}
+
+/// Rasterises the icons of the given applications again when their cached copy
+/// is gone, e.g. after the cache directory was cleared. Returns how many icons are ready.
+pub fn prewarm_icon_cache(applications: &[Application], size: u16, icon_theme: &str) -> usize {
+    let scale = scale_factor();
+    let icon_paths: HashSet<PathBuf> = applications
+        .par_iter()
+        .filter(|app| !app.icon.is_empty())
+        .filter_map(|app| {
+            Some(PathBuf::from(&app.icon_path))
+                .filter(|path| path.is_file())
+                .or_else(|| prepare_icon(&app.icon, size, scale, icon_theme))
+        })
+        .collect();
+    icon_paths.len()
+}
+
+/// Handler of the `cyberdeck-icon` protocol.
+pub fn handle_icon_request(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    // absolute paths arrive with their slashes encoded, after the leading one
+    let Some(id) = percent_decode(request.uri().path()) else {
+        return text_response(StatusCode::BAD_REQUEST, "invalid icon id encoding");
+    };
+    let id = id.strip_prefix('/').unwrap_or(&id);
+    let query = request.uri().query();
+    let Some(size) = parse_query_number(query, "size", DEFAULT_ICON_SIZE, MAX_ICON_SIZE) else {
+        return text_response(StatusCode::BAD_REQUEST, "invalid icon size");
+    };
+    // the scale of the window that requested the icon, which may have moved
+    // to another screen since the last scale change was seen here
+    let Some(scale) = parse_query_number(query, "scale", scale_factor(), MAX_ICON_SCALE) else {
+        return text_response(StatusCode::BAD_REQUEST, "invalid icon scale");
+    };
+
+    let Some((prepared_path, prepared_size)) = KNOWN_ICONS
+        .lock()
+        .ok()
+        .and_then(|known_icons| known_icons.get(id).cloned())
+    else {
+        return text_response(StatusCode::NOT_FOUND, "unknown icon");
+    };
+
+    // rasterised by the scan, other sizes and scales only the first time they are requested
+    let icon_path = if prepared_size == u32::from(size) * scale && prepared_path.is_file() {
+        prepared_path
+    } else {
+        let icon_theme = APP_CONFIG
+            .lock()
+            .map(|config| config.icon_theme.clone())
+            .unwrap_or_default();
+        match prepare_icon(id, size, scale, &icon_theme) {
+            Some(icon_path) => icon_path,
+            None => return text_response(StatusCode::NOT_FOUND, "icon not found"),
+        }
+    };
+
+    let etag = match std::fs::metadata(&icon_path) {
+        Ok(metadata) => {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs())
+                .unwrap_or_default();
+            format!("\"{modified:x}-{len:x}\"", len = metadata.len())
+        }
+        Err(e) => {
+            warn!("Failed to read icon {icon_path:?}: {e}");
+            return text_response(StatusCode::NOT_FOUND, "icon not found");
+        }
+    };
+    let is_unchanged = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|if_none_match| if_none_match.as_bytes() == etag.as_bytes());
+    if is_unchanged {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .header(header::CACHE_CONTROL, CACHE_CONTROL)
+            .body(vec![])
+            .expect("Failed to build icon response");
+    }
+
+    match std::fs::read(&icon_path) {
+        Ok(content) => {
+            let mime = mime_guess::from_path(&icon_path).first_or_octet_stream();
+            Response::builder()
+                .header(header::CONTENT_TYPE, mime.essence_str())
+                .header(header::ETAG, etag)
+                .header(header::CACHE_CONTROL, CACHE_CONTROL)
+                .body(content)
+                .expect("Failed to build icon response")
+        }
+        Err(e) => {
+            warn!("Failed to read icon {icon_path:?}: {e}");
+            text_response(StatusCode::NOT_FOUND, "icon not found")
+        }
+    }
+}
+
+// `default` when the parameter is missing, `None` when it is out of `1..=max`
+fn parse_query_number<T>(query: Option<&str>, name: &str, default: T, max: T) -> Option<T>
+where
+    T: std::str::FromStr + PartialOrd + From<u8>,
+{
+    let Some(value) = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+    else {
+        return Some(default);
+    };
+    value
+        .parse()
+        .ok()
+        .filter(|value| (T::from(1)..=max).contains(value))
+}
@@ -0,0 +1,321 @@
+use crate::layers::InvalidValue;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use ts_rs::TS;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/Action.ts")]
+pub enum Action {
+    /// select the next application
+    Next,
+    /// select the previous application
+    Prev,
+    NextPage,
+    PrevPage,
+    Launch,
+    /// launch the selected application inside `terminal_app`
+    LaunchInTerminal,
+    /// close the launcher
+    Hide,
+    /// removes a binding inherited from the preset
+    Unbound,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/KeybindingsPreset.ts")]
+pub enum KeybindingsPreset {
+    Default,
+    /// adds Ctrl+j/k/h/l and Ctrl+f/b
+    Vim,
+    /// adds Ctrl+n/p, Ctrl+v/Alt+v, Ctrl+m and Ctrl+g
+    Emacs,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/types/KeybindingsConfig.ts")]
+pub struct KeybindingsConfig {
+    /// Built-in bindings the custom ones are applied on top of
+    pub preset: KeybindingsPreset,
+    /// Key chord, e.g. "Ctrl+Shift+k", -> action
+    pub bindings: BTreeMap<String, Action>,
+}
+
+impl Default for KeybindingsConfig {
+    fn default() -> Self {
+        KeybindingsConfig {
+            preset: KeybindingsPreset::Default,
+            bindings: BTreeMap::new(),
+        }
+    }
+}
+
+const DEFAULT_BINDINGS: &[(&str, Action)] = &[
+    ("ArrowDown", Action::Next),
+    ("ArrowUp", Action::Prev),
+    ("ArrowRight", Action::NextPage),
+    ("ArrowLeft", Action::PrevPage),
+    ("PageDown", Action::NextPage),
+    ("PageUp", Action::PrevPage),
+    ("Enter", Action::Launch),
+    ("Ctrl+Enter", Action::LaunchInTerminal),
+    ("Escape", Action::Hide),
+];
+
+// letters alone are typed into the search field, so the presets use Ctrl
+const VIM_BINDINGS: &[(&str, Action)] = &[
+    ("Ctrl+j", Action::Next),
+    ("Ctrl+k", Action::Prev),
+    ("Ctrl+l", Action::NextPage),
+    ("Ctrl+h", Action::PrevPage),
+    ("Ctrl+f", Action::NextPage),
+    ("Ctrl+b", Action::PrevPage),
+];
+
+const EMACS_BINDINGS: &[(&str, Action)] = &[
+    ("Ctrl+n", Action::Next),
+    ("Ctrl+p", Action::Prev),
+    ("Ctrl+v", Action::NextPage),
+    ("Alt+v", Action::PrevPage),
+    ("Ctrl+m", Action::Launch),
+    ("Ctrl+g", Action::Hide),
+];
+
+impl KeybindingsConfig {
+    /// Merges the preset with the custom bindings into canonical chord -> action.
+    /// Invalid chords are skipped and reported in the second element.
+    pub fn resolve(&self) -> (BTreeMap<String, Action>, Vec<String>) {
+        let preset_bindings = match self.preset {
+            KeybindingsPreset::Default => &[][..],
+            KeybindingsPreset::Vim => VIM_BINDINGS,
+            KeybindingsPreset::Emacs => EMACS_BINDINGS,
+        };
+
+        let mut resolved = BTreeMap::new();
+        for (chord, action) in DEFAULT_BINDINGS.iter().chain(preset_bindings) {
+            let chord = parse_chord(chord).expect("Invalid built-in key chord");
+            resolved.insert(chord, *action);
+        }
+
+        let mut errors = vec![];
+        for (chord, action) in &self.bindings {
+            match parse_chord(chord) {
+                Ok(chord) if *action == Action::Unbound => {
+                    resolved.remove(&chord);
+                }
+                Ok(chord) => {
+                    resolved.insert(chord, *action);
+                }
+                Err(e) => errors.push(format!("Invalid key chord '{chord}': {e}")),
+            }
+        }
+
+        (resolved, errors)
+    }
+
+    pub fn invalid_values(&self) -> Vec<InvalidValue> {
+        self.bindings
+            .keys()
+            .filter_map(|chord| {
+                let e = parse_chord(chord).err()?;
+                Some(InvalidValue::new(
+                    &["keybindings", "bindings", chord],
+                    format!("Invalid key chord '{chord}': {e}"),
+                ))
+            })
+            .collect()
+    }
+}
+
+// canonical order of the modifiers, matching how the frontend builds chords
+const MODIFIERS: [&str; 4] = ["Ctrl", "Alt", "Shift", "Super"];
+const SHIFT: usize = 2;
+
+// the frontend names keys after their position on a US keyboard, so a symbol
+// typed with Shift is bound as Shift and the key it is on
+const SHIFTED_SYMBOLS: [(char, char); 21] = [
+    ('!', '1'),
+    ('@', '2'),
+    ('#', '3'),
+    ('$', '4'),
+    ('%', '5'),
+    ('^', '6'),
+    ('&', '7'),
+    ('*', '8'),
+    ('(', '9'),
+    (')', '0'),
+    ('_', '-'),
+    ('+', '='),
+    ('{', '['),
+    ('}', ']'),
+    ('|', '\\'),
+    (':', ';'),
+    ('"', '\''),
+    ('<', ','),
+    ('>', '.'),
+    ('?', '/'),
+    ('~', '`'),
+];
+
+/// Turns a chord like "shift+ctrl+K" into its canonical form "Ctrl+Shift+k",
+/// using `KeyboardEvent.key` names for named keys and the unshifted character
+/// of a US keyboard otherwise, e.g. "Ctrl+!" becomes "Ctrl+Shift+1".
+pub fn parse_chord(chord: &str) -> Result<String, String> {
+    let parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+    // "Ctrl++" binds the plus key, Shift and `=` on a US keyboard
+    let (key, modifier_parts) = match parts.as_slice() {
+        [modifiers @ .., "", ""] => ("+", modifiers),
+        [modifiers @ .., key] => (*key, modifiers),
+        [] => unreachable!("split always yields at least one part"),
+    };
+
+    let mut modifiers = [false; MODIFIERS.len()];
+    for modifier in modifier_parts {
+        let index = match modifier.to_lowercase().as_str() {
+            "ctrl" | "control" => 0,
+            "alt" | "option" => 1,
+            "shift" => SHIFT,
+            "super" | "meta" | "cmd" | "win" => 3,
+            _ => return Err(format!("unknown modifier '{modifier}'")),
+        };
+        if modifiers[index] {
+            return Err(format!("repeated modifier '{modifier}'"));
+        }
+        modifiers[index] = true;
+    }
+
+    let mut key = parse_key(key)?;
+    if let Some((_, unshifted)) = SHIFTED_SYMBOLS
+        .iter()
+        .find(|(shifted, _)| key.chars().eq([*shifted]))
+    {
+        key = unshifted.to_string();
+        modifiers[SHIFT] = true;
+    }
+
+    let mut canonical: Vec<String> = MODIFIERS
+        .iter()
+        .zip(modifiers)
+        .filter(|(_, pressed)| *pressed)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    canonical.push(key);
+    Ok(canonical.join("+"))
+}
+
+fn parse_key(key: &str) -> Result<String, String> {
+    if key.is_empty() {
+        return Err(String::from("missing key"));
+    }
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(c.to_lowercase().to_string());
+    }
+
+    let name = match key.to_lowercase().as_str() {
+        "enter" | "return" => "Enter",
+        "escape" | "esc" => "Escape",
+        "tab" => "Tab",
+        "space" => "Space",
+        "backspace" => "Backspace",
+        "delete" | "del" => "Delete",
+        "insert" | "ins" => "Insert",
+        "home" => "Home",
+        "end" => "End",
+        "pageup" | "pgup" => "PageUp",
+        "pagedown" | "pgdown" => "PageDown",
+        "arrowup" | "up" => "ArrowUp",
+        "arrowdown" | "down" => "ArrowDown",
+        "arrowleft" | "left" => "ArrowLeft",
+        "arrowright" | "right" => "ArrowRight",
+        "plus" => "+",
+        function_key
+            if function_key.starts_with('f')
+                && function_key[1..]
+                    .parse::<u8>()
+                    .is_ok_and(|n| (1..=24).contains(&n)) =>
+        {
+            return Ok(function_key.to_uppercase());
+        }
+        _ => return Err(format!("unknown key '{key}'")),
+    };
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_chord_orders_modifiers_and_names_keys() {
+        assert_eq!(
+            parse_chord("shift+ctrl+K"),
+            Ok(String::from("Ctrl+Shift+k"))
+        );
+        assert_eq!(parse_chord("Meta + esc"), Ok(String::from("Super+Escape")));
+        assert_eq!(
+            parse_chord("control+pgdown"),
+            Ok(String::from("Ctrl+PageDown"))
+        );
+        assert_eq!(parse_chord("alt+f12"), Ok(String::from("Alt+F12")));
+        assert_eq!(parse_chord("Space"), Ok(String::from("Space")));
+    }
+
+    #[test]
+    fn parse_chord_binds_shifted_symbols_to_their_key() {
+        assert_eq!(parse_chord("Ctrl+!"), Ok(String::from("Ctrl+Shift+1")));
+        assert_eq!(
+            parse_chord("Ctrl+Shift+1"),
+            Ok(String::from("Ctrl+Shift+1"))
+        );
+        assert_eq!(parse_chord("Shift+?"), Ok(String::from("Shift+/")));
+        assert_eq!(parse_chord("Ctrl++"), Ok(String::from("Ctrl+Shift+=")));
+        assert_eq!(parse_chord("Ctrl+plus"), Ok(String::from("Ctrl+Shift+=")));
+        assert_eq!(parse_chord("Alt+-"), Ok(String::from("Alt+-")));
+    }
+
+    #[test]
+    fn parse_chord_rejects_invalid_chords() {
+        assert!(parse_chord("").is_err());
+        assert!(parse_chord("Ctrl+").is_err());
+        assert!(parse_chord("Hyper+k").is_err());
+        assert!(parse_chord("Ctrl+ctrl+k").is_err());
+        assert!(parse_chord("Ctrl+f25").is_err());
+        assert!(parse_chord("Ctrl+Enterr").is_err());
+    }
+
+    #[test]
+    fn built_in_chords_are_canonical() {
+        for (chord, _) in DEFAULT_BINDINGS
+            .iter()
+            .chain(VIM_BINDINGS)
+            .chain(EMACS_BINDINGS)
+        {
+            assert_eq!(parse_chord(chord).as_deref(), Ok(*chord));
+        }
+    }
+
+    #[test]
+    fn resolve_applies_custom_bindings_over_the_preset() {
+        let config = KeybindingsConfig {
+            preset: KeybindingsPreset::Vim,
+            bindings: BTreeMap::from([
+                (String::from("ctrl+J"), Action::Launch),
+                (String::from("Escape"), Action::Unbound),
+                (String::from("Ctrl+?"), Action::Hide),
+                (String::from("Bogus+x"), Action::Next),
+            ]),
+        };
+        let (resolved, errors) = config.resolve();
+        assert_eq!(resolved.get("Ctrl+j"), Some(&Action::Launch));
+        assert_eq!(resolved.get("Ctrl+k"), Some(&Action::Prev));
+        assert_eq!(resolved.get("Ctrl+Shift+/"), Some(&Action::Hide));
+        assert_eq!(resolved.get("Escape"), None);
+        assert_eq!(resolved.get("Enter"), Some(&Action::Launch));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(config.invalid_values().len(), 1);
+    }
+}
@@ -0,0 +1,441 @@
+use log::warn;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use ts_rs::TS;
+
+/// Where a config value came from, from the lowest to the highest precedence.
+#[derive(Debug, Serialize, Clone, TS)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/ConfigSource.ts")]
+pub enum ConfigSource {
+    Default,
+    System { path: PathBuf },
+    User { path: PathBuf },
+    Profile { name: String, path: PathBuf },
+    Env { variable: String },
+    Cli { argument: String },
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::System { path } => write!(f, "system config {path:?}"),
+            ConfigSource::User { path } => write!(f, "user config {path:?}"),
+            ConfigSource::Profile { name, path } => write!(f, "profile '{name}' {path:?}"),
+            ConfigSource::Env { variable } => write!(f, "environment variable {variable}"),
+            ConfigSource::Cli { argument } => write!(f, "command line argument '{argument}'"),
+        }
+    }
+}
+
+impl ConfigSource {
+    /// Whether values from this source win over the user config.
+    pub fn overrides_user_config(&self) -> bool {
+        matches!(
+            self,
+            ConfigSource::Profile { .. } | ConfigSource::Env { .. } | ConfigSource::Cli { .. }
+        )
+    }
+}
+
+/// A partial config object together with its origin.
+pub struct ConfigLayer {
+    pub source: ConfigSource,
+    pub value: Value,
+}
+
+pub struct MergedConfig {
+    pub value: Value,
+    /// dotted key path (e.g. `lookup_dirs.extend`) -> source of its value
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+/// A value that deserializes but can't be used, like a number out of range.
+#[derive(Debug)]
+pub struct InvalidValue {
+    /// e.g. `["ui", "icon_size"]`
+    pub key: Vec<String>,
+    pub message: String,
+}
+
+impl InvalidValue {
+    pub fn new(key: &[&str], message: String) -> Self {
+        InvalidValue {
+            key: key.iter().map(|key| key.to_string()).collect(),
+            message,
+        }
+    }
+}
+
+/// Replaces the value at `key` with its default, or removes it when there is none,
+/// like for an entry of a map. Returns false when the value already is the default.
+pub fn reset_to_default(merged: &mut MergedConfig, key: &[String], defaults: &Value) -> bool {
+    let Some((last, parents)) = key.split_last() else {
+        return false;
+    };
+    let Some(Value::Object(map)) = lookup_mut(&mut merged.value, parents) else {
+        return false;
+    };
+    let default = lookup(defaults, key);
+    if map.get(last) == default {
+        return false;
+    }
+
+    let path = key.join(".");
+    let nested_prefix = format!("{path}.");
+    merged
+        .sources
+        .retain(|source_key, _| *source_key != path && !source_key.starts_with(&nested_prefix));
+    match default {
+        Some(default) => {
+            record_sources(default, &path, &ConfigSource::Default, &mut merged.sources);
+            map.insert(last.clone(), default.clone());
+        }
+        None => {
+            map.remove(last);
+        }
+    }
+    true
+}
+
+/// Deep merges the layers in order, so later layers win.
+/// Objects are merged key by key, any other value (including arrays) is replaced as a whole.
+pub fn merge_layers(layers: Vec<ConfigLayer>) -> MergedConfig {
+    let mut merged = MergedConfig {
+        value: Value::Object(Map::new()),
+        sources: BTreeMap::new(),
+    };
+    for layer in layers {
+        merge_value(
+            &mut merged.value,
+            layer.value,
+            "",
+            &layer.source,
+            &mut merged.sources,
+        );
+    }
+    merged
+}
+
+fn merge_value(
+    target: &mut Value,
+    layer: Value,
+    path: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match (target, layer) {
+        (Value::Object(target_map), Value::Object(layer_map)) => {
+            for (key, layer_value) in layer_map {
+                let key_path = join_path(path, &key);
+                let target_value = target_map.entry(key).or_insert(Value::Null);
+                merge_value(target_value, layer_value, &key_path, source, sources);
+            }
+        }
+        (target, layer) => {
+            // the replaced value might have been an object with its own sources
+            let nested_prefix = format!("{path}.");
+            sources.retain(|key, _| !key.starts_with(&nested_prefix));
+            record_sources(&layer, path, source, sources);
+            *target = layer;
+        }
+    }
+}
+
+/// Dotted paths of the values in `value`, like the keys of `MergedConfig::sources`.
+pub fn key_paths(value: &Value) -> Vec<String> {
+    let mut paths = BTreeMap::new();
+    record_sources(value, "", &ConfigSource::Default, &mut paths);
+    paths.into_keys().collect()
+}
+
+fn record_sources(
+    value: &Value,
+    path: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, nested) in map {
+                record_sources(nested, &join_path(path, key), source, sources);
+            }
+        }
+        _ => {
+            sources.insert(path.to_string(), source.clone());
+        }
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// One layer per `CYBERDECK_*` variable that names a known option.
+/// Nested keys are separated by a double underscore, e.g. `CYBERDECK_LOOKUP_DIRS__EXTEND`.
+pub fn env_layers(defaults: &Value) -> Vec<ConfigLayer> {
+    let mut variables: Vec<(String, String)> = std::env::vars()
+        .filter(|(variable, _)| variable.starts_with("CYBERDECK_"))
+        .collect();
+    // keeps the result independent of the environment order
+    variables.sort();
+
+    variables
+        .into_iter()
+        .filter_map(|(variable, raw_value)| {
+            let key_path: Vec<String> = variable["CYBERDECK_".len()..]
+                .to_lowercase()
+                .split("__")
+                .map(String::from)
+                .collect();
+            // other variables like CYBERDECK_PROFILE share the prefix but aren't options
+            let default = lookup(defaults, &key_path)?;
+            if is_version(&key_path) {
+                warn!("Ignoring {variable}: only migrations change the config version");
+                return None;
+            }
+            Some(ConfigLayer {
+                value: nest(&key_path, parse_override_value(&raw_value, default)),
+                source: ConfigSource::Env { variable },
+            })
+        })
+        .collect()
+}
+
+/// One layer per `--set key.path=value` argument.
+pub fn cli_layers(overrides: &[String], defaults: &Value) -> Vec<ConfigLayer> {
+    overrides
+        .iter()
+        .filter_map(|argument| {
+            let Some((key, raw_value)) = argument.split_once('=') else {
+                warn!("Ignoring '--set {argument}': expected KEY=VALUE");
+                return None;
+            };
+            let key_path: Vec<String> = key.trim().split('.').map(String::from).collect();
+            let Some(default) = lookup(defaults, &key_path) else {
+                warn!("Ignoring '--set {argument}': unknown config key `{key}`");
+                return None;
+            };
+            if is_version(&key_path) {
+                warn!("Ignoring '--set {argument}': only migrations change the config version");
+                return None;
+            }
+            Some(ConfigLayer {
+                value: nest(&key_path, parse_override_value(raw_value, default)),
+                source: ConfigSource::Cli {
+                    argument: format!("--set {argument}"),
+                },
+            })
+        })
+        .collect()
+}
+
+// a version from another layer would skip or repeat migrations
+fn is_version(key_path: &[String]) -> bool {
+    key_path == ["version"]
+}
+
+fn lookup<'a>(value: &'a Value, key_path: &[String]) -> Option<&'a Value> {
+    key_path
+        .iter()
+        .try_fold(value, |current, key| current.as_object()?.get(key))
+}
+
+fn lookup_mut<'a>(value: &'a mut Value, key_path: &[String]) -> Option<&'a mut Value> {
+    key_path
+        .iter()
+        .try_fold(value, |current, key| current.as_object_mut()?.get_mut(key))
+}
+
+fn nest(key_path: &[String], value: Value) -> Value {
+    key_path.iter().rev().fold(value, |nested, key| {
+        let mut map = Map::new();
+        map.insert(key.clone(), nested);
+        Value::Object(map)
+    })
+}
+
+/// Interprets a raw string using the type of the default value:
+/// strings are taken verbatim, lists may be colon separated (like `PATH`),
+/// everything else is parsed as JSON.
+fn parse_override_value(raw_value: &str, default: &Value) -> Value {
+    match default {
+        Value::String(_) => Value::String(raw_value.to_string()),
+        Value::Array(_) if !raw_value.trim_start().starts_with('[') => Value::Array(
+            raw_value
+                .split(':')
+                .filter(|part| !part.is_empty())
+                .map(|part| Value::String(part.to_string()))
+                .collect(),
+        ),
+        // an unparsable value is kept as a string, so deserialization reports the key
+        _ => serde_json::from_str(raw_value).unwrap_or_else(|_| Value::String(raw_value.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn layer(source: ConfigSource, value: Value) -> ConfigLayer {
+        ConfigLayer { source, value }
+    }
+
+    fn user() -> ConfigSource {
+        ConfigSource::User {
+            path: PathBuf::from("/home/user/.config/cyberdeck/config.toml"),
+        }
+    }
+
+    #[test]
+    fn merge_layers_merges_objects_and_replaces_other_values() {
+        let merged = merge_layers(vec![
+            layer(
+                ConfigSource::Default,
+                json!({
+                    "terminal_app": "kitty",
+                    "ui": { "apps_per_page": 5, "theme": "" },
+                    "lookup_dirs": { "extend": [] },
+                }),
+            ),
+            layer(
+                user(),
+                json!({ "ui": { "apps_per_page": 8 }, "lookup_dirs": { "extend": ["/opt/apps"] } }),
+            ),
+            layer(
+                ConfigSource::Cli {
+                    argument: String::from("--set lookup_dirs.extend=/srv/apps"),
+                },
+                json!({ "lookup_dirs": { "extend": ["/srv/apps"] } }),
+            ),
+        ]);
+
+        assert_eq!(
+            merged.value,
+            json!({
+                "terminal_app": "kitty",
+                "ui": { "apps_per_page": 8, "theme": "" },
+                "lookup_dirs": { "extend": ["/srv/apps"] },
+            })
+        );
+        assert!(matches!(
+            merged.sources.get("terminal_app"),
+            Some(ConfigSource::Default)
+        ));
+        assert!(matches!(
+            merged.sources.get("ui.apps_per_page"),
+            Some(ConfigSource::User { .. })
+        ));
+        assert!(matches!(
+            merged.sources.get("ui.theme"),
+            Some(ConfigSource::Default)
+        ));
+        assert!(matches!(
+            merged.sources.get("lookup_dirs.extend"),
+            Some(ConfigSource::Cli { .. })
+        ));
+    }
+
+    #[test]
+    fn merge_layers_drops_sources_of_a_replaced_object() {
+        let merged = merge_layers(vec![
+            layer(
+                ConfigSource::Default,
+                json!({ "palette": { "source": "none", "path": "" } }),
+            ),
+            layer(user(), json!({ "palette": "pywal" })),
+        ]);
+
+        assert_eq!(merged.value, json!({ "palette": "pywal" }));
+        assert_eq!(merged.sources.keys().collect::<Vec<_>>(), ["palette"]);
+    }
+
+    #[test]
+    fn cli_layers_parse_values_like_their_defaults() {
+        let defaults = json!({
+            "version": 1,
+            "terminal_app": "kitty",
+            "ui": { "apps_per_page": 5 },
+            "lookup_dirs": { "extend": [] },
+        });
+        let overrides = [
+            String::from("terminal_app=true"),
+            String::from("ui.apps_per_page=8"),
+            String::from("lookup_dirs.extend=/opt/apps:/srv/apps"),
+            String::from("ui.unknown=1"),
+            String::from("missing_equals_sign"),
+            String::from("version=0"),
+        ];
+        let values: Vec<Value> = cli_layers(&overrides, &defaults)
+            .into_iter()
+            .map(|layer| layer.value)
+            .collect();
+
+        assert_eq!(
+            values,
+            [
+                json!({ "terminal_app": "true" }),
+                json!({ "ui": { "apps_per_page": 8 } }),
+                json!({ "lookup_dirs": { "extend": ["/opt/apps", "/srv/apps"] } }),
+            ]
+        );
+    }
+
+    #[test]
+    fn reset_to_default_replaces_only_the_invalid_key() {
+        let defaults =
+            json!({ "ui": { "icon_size": 48, "theme": "" }, "keybindings": { "bindings": {} } });
+        let mut merged = merge_layers(vec![
+            layer(ConfigSource::Default, defaults.clone()),
+            layer(
+                user(),
+                json!({
+                    "ui": { "icon_size": 0, "theme": "neon" },
+                    "keybindings": { "bindings": { "Bogus+x": "next", "Ctrl+q": "hide" } },
+                }),
+            ),
+        ]);
+        let key = |path: &str| path.split('.').map(String::from).collect::<Vec<_>>();
+
+        assert!(reset_to_default(
+            &mut merged,
+            &key("ui.icon_size"),
+            &defaults
+        ));
+        assert!(reset_to_default(
+            &mut merged,
+            &key("keybindings.bindings.Bogus+x"),
+            &defaults
+        ));
+        assert!(!reset_to_default(
+            &mut merged,
+            &key("ui.icon_size"),
+            &defaults
+        ));
+
+        assert_eq!(
+            merged.value,
+            json!({
+                "ui": { "icon_size": 48, "theme": "neon" },
+                "keybindings": { "bindings": { "Ctrl+q": "hide" } },
+            })
+        );
+        assert!(matches!(
+            merged.sources.get("ui.icon_size"),
+            Some(ConfigSource::Default)
+        ));
+        assert!(matches!(
+            merged.sources.get("ui.theme"),
+            Some(ConfigSource::User { .. })
+        ));
+        assert!(!merged.sources.contains_key("keybindings.bindings.Bogus+x"));
+    }
+}
@@ -1,18 +1,41 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod app_index;
+mod app_watcher;
+mod cli;
 mod commands;
 mod config;
-
+mod config_file;
+mod doctor;
+mod history;
+mod icon_cache;
+mod icon_theme;
+mod icons;
+mod keybindings;
+mod layers;
+mod migrations;
+mod palette;
+mod paths;
+mod placeholder;
+mod protocol;
+mod stable_hash;
+mod theme;
+
+use clap::Parser;
 use gtk::prelude::*;
 use gtk_layer_shell::{Edge, Layer, LayerShell};
 use tauri::Manager;
 
 fn main() {
-    if let Ok(_config_guard) = config::APP_CONFIG.lock() {
-        println!("App started with config");
-    } else {
-        println!("Failed to init config");
+    let cli = cli::Cli::parse();
+    // before anything reads the config or a cache
+    paths::set_path_overrides(cli.path_overrides());
+    config::set_config_options(cli.config_options());
+    if let Some(command) = cli.command {
+        // config warnings go to stderr, so they don't mix with the output
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+        std::process::exit(cli::run_command(command));
     }
 
     let mut builder = tauri::Builder::default();
@@ -46,7 +69,20 @@ fn main() {
     }
 
     builder
+        .register_uri_scheme_protocol(icons::ICON_SCHEME, |_ctx, request| {
+            icons::handle_icon_request(&request)
+        })
+        .register_uri_scheme_protocol(theme::THEME_SCHEME, |_ctx, request| {
+            theme::handle_theme_request(&request)
+        })
         .setup(|app| {
+            // loaded once the plugins set up logging, so warnings about the config are kept
+            if let Ok(_config_guard) = config::APP_CONFIG.lock() {
+                println!("App started with config");
+            } else {
+                println!("Failed to init config");
+            }
+
             // setting up gtk layer
             let main_webview = app.get_webview_window("main").unwrap();
             let _ = main_webview.hide();
@@ -83,6 +119,20 @@ fn main() {
 
             gtk_window.show_all();
 
+            // icons are looked up for the physical size on HiDPI screens
+            icons::set_scale_factor(gtk_window.scale_factor());
+            let app_handle = app.handle().clone();
+            gtk_window.connect_scale_factor_notify(move |window| {
+                icons::on_scale_factor_changed(&app_handle, window.scale_factor());
+            });
+
+            // kept in the app state, the watcher stops when dropped
+            if let Some(theme_watcher) = theme::watch_themes(app.handle().clone()) {
+                app.manage(theme_watcher);
+            }
+            palette::watch_palette(app.handle().clone());
+            app_watcher::watch_applications(app.handle().clone());
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -91,8 +141,16 @@ fn main() {
             commands::get_desktop_applications,
             commands::is_dev,
             commands::try_get_cached_applications,
+            commands::load_applications,
+            commands::stream_applications,
             config::get_cyberdeck_config,
             config::get_client_config,
+            config::merge_config_defaults,
+            config::get_effective_config,
+            config::set_cyberdeck_config,
+            doctor::get_diagnostics,
+            icons::get_icon_scale,
+            palette::get_palette,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri app");
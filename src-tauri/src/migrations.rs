@@ -0,0 +1,169 @@
+use log::{info, warn};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Upgrades a config object by one version.
+type Migration = fn(&mut Map<String, Value>);
+
+// `MIGRATIONS[n]` upgrades a version `n` config to version `n + 1`.
+// Never edit or reorder existing entries, only append new ones.
+const MIGRATIONS: &[Migration] = &[migrate_unversioned_config];
+
+pub const CURRENT_CONFIG_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Brings the raw config up to `CURRENT_CONFIG_VERSION`.
+/// Returns the version the config had before, or `None` if nothing changed.
+pub fn migrate(config: &mut Value) -> Result<Option<u32>, String> {
+    let Value::Object(config_map) = config else {
+        return Err(String::from("Config is not a JSON object"));
+    };
+
+    // configs written before versioning was introduced have no `version` key, but
+    // neither does a new config that leaves it out, so those are only outdated
+    // when a migration actually changes them
+    let version = match config_map.get("version") {
+        Some(value) => Some(
+            value
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| format!("Invalid config version: {value}"))?,
+        ),
+        None => None,
+    };
+    let from_version = version.unwrap_or(0);
+
+    if from_version > CURRENT_CONFIG_VERSION {
+        warn!(
+            "Config version {from_version} is newer than the supported {CURRENT_CONFIG_VERSION}, \
+            loading it as is"
+        );
+        return Ok(None);
+    }
+    if from_version == CURRENT_CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    let unversioned = version.is_none().then(|| config_map.clone());
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(from_version as usize) {
+        migration(config_map);
+        if version.is_some() {
+            info!(
+                "Migrating config from version {from} to {to}",
+                to = from + 1
+            );
+        }
+    }
+    if unversioned.is_some_and(|unversioned| unversioned == *config_map) {
+        return Ok(None);
+    }
+    config_map.insert(String::from("version"), Value::from(CURRENT_CONFIG_VERSION));
+
+    Ok(Some(from_version))
+}
+
+/// Copies the config file next to itself before it gets rewritten,
+/// e.g. `config.json` -> `config.json.v0.bak`.
+pub fn backup_config_file(config_path: &Path, version: u32) -> Result<PathBuf, String> {
+    let mut backup_name = config_path
+        .file_name()
+        .ok_or_else(|| format!("Invalid config path {config_path:?}"))?
+        .to_os_string();
+    backup_name.push(format!(".v{version}.bak"));
+    let backup_path = config_path.with_file_name(backup_name);
+
+    fs::copy(config_path, &backup_path)
+        .map_err(|e| format!("Failed to back up {config_path:?} to {backup_path:?}: {e}"))?;
+    Ok(backup_path)
+}
+
+// a value already under the new name wins
+fn rename_key(config: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = config.shift_remove(from) {
+        config.entry(to).or_insert(value);
+    }
+}
+
+// v0 -> v1, from the configs written before versioning:
+// - the icon theme is no longer KDE specific
+// - `lookup_dirs` used to be the complete list of directories,
+//   it becomes `{ "extend": [...], "override": [...] }`
+// - display options are grouped in a `ui` section
+fn migrate_unversioned_config(config: &mut Map<String, Value>) {
+    rename_key(config, "kde_icon_theme", "icon_theme");
+
+    if let Some(dirs @ Value::Array(_)) = config.get("lookup_dirs") {
+        let mut lookup_dirs = Map::new();
+        lookup_dirs.insert(String::from("extend"), Value::Array(vec![]));
+        lookup_dirs.insert(String::from("override"), dirs.clone());
+        config.insert(String::from("lookup_dirs"), Value::Object(lookup_dirs));
+    }
+
+    if let Some(apps_per_page) = config.shift_remove("apps_per_page") {
+        let ui = config
+            .entry("ui")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(ui) = ui {
+            ui.entry("apps_per_page").or_insert(apps_per_page);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrates_a_v0_config() {
+        let mut config = json!({
+            "kde_icon_theme": "Papirus",
+            "lookup_dirs": ["/a"],
+            "apps_per_page": 7
+        });
+        assert_eq!(migrate(&mut config), Ok(Some(0)));
+        assert_eq!(
+            config,
+            json!({
+                "icon_theme": "Papirus",
+                "lookup_dirs": { "extend": [], "override": ["/a"] },
+                "ui": { "apps_per_page": 7 },
+                "version": CURRENT_CONFIG_VERSION
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_the_new_value_of_a_renamed_key() {
+        let mut config = json!({ "version": 0, "kde_icon_theme": "old", "icon_theme": "new" });
+        migrate(&mut config).unwrap();
+        assert_eq!(config["icon_theme"], "new");
+        assert!(config.get("kde_icon_theme").is_none());
+    }
+
+    #[test]
+    fn leaves_current_configs_alone() {
+        // old keys are no longer moved once the config has a version
+        let current =
+            json!({ "version": CURRENT_CONFIG_VERSION, "kde_icon_theme": "x", "apps_per_page": 7 });
+        let mut config = current.clone();
+        assert_eq!(migrate(&mut config), Ok(None));
+        assert_eq!(config, current);
+
+        let unversioned = json!({ "terminal_app": "foot", "ui": { "apps_per_page": 7 } });
+        let mut config = unversioned.clone();
+        assert_eq!(migrate(&mut config), Ok(None));
+        assert_eq!(config, unversioned);
+
+        let newer = json!({ "version": CURRENT_CONFIG_VERSION + 1 });
+        let mut config = newer.clone();
+        assert_eq!(migrate(&mut config), Ok(None));
+        assert_eq!(config, newer);
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        assert!(migrate(&mut json!({ "version": "1" })).is_err());
+        assert!(migrate(&mut json!([])).is_err());
+    }
+}
@@ -0,0 +1,479 @@
+use crate::config::APP_CONFIG;
+use crate::layers::InvalidValue;
+use crate::paths;
+use log::{error, info};
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::Emitter;
+use ts_rs::TS;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/PaletteSource.ts")]
+pub enum PaletteSource {
+    /// keep the colours of `styles.css` and the theme
+    None,
+    /// `colors.json` written by pywal
+    Pywal,
+    /// a base16 scheme with `base00` to `base0F`
+    Base16,
+    /// the output of `matugen --json hex` or colours written by a matugen template
+    Matugen,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../../src/types/PaletteScheme.ts")]
+pub enum PaletteScheme {
+    Dark,
+    Light,
+}
+
+impl PaletteScheme {
+    fn key(self) -> &'static str {
+        match self {
+            PaletteScheme::Dark => "dark",
+            PaletteScheme::Light => "light",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+#[serde(default)]
+#[ts(export, export_to = "../../src/types/PaletteConfig.ts")]
+pub struct PaletteConfig {
+    /// Format of the palette file
+    pub source: PaletteSource,
+    /// YAML or JSON palette file, `~/` is expanded.
+    /// Optional for pywal, which defaults to `~/.cache/wal/colors.json`
+    pub path: String,
+    /// Which colours to use from a matugen palette, which has a dark and a light scheme
+    pub scheme: PaletteScheme,
+}
+
+impl Default for PaletteConfig {
+    fn default() -> Self {
+        PaletteConfig {
+            source: PaletteSource::None,
+            path: String::new(),
+            scheme: PaletteScheme::Dark,
+        }
+    }
+}
+
+impl PaletteConfig {
+    pub fn invalid_values(&self) -> Vec<InvalidValue> {
+        let needs_path = matches!(self.source, PaletteSource::Base16 | PaletteSource::Matugen);
+        if needs_path && self.path.trim().is_empty() {
+            // the default path is empty too, so the source falls back instead
+            return vec![InvalidValue::new(
+                &["palette", "source"],
+                String::from("`palette.path` must be set for base16 and matugen palettes"),
+            )];
+        }
+        vec![]
+    }
+
+    /// The palette file, or `None` when no palette is used.
+    pub fn resolve_path(&self) -> Option<PathBuf> {
+        if self.source == PaletteSource::None {
+            return None;
+        }
+        if !self.path.is_empty() {
+            return Some(expand_home(&self.path));
+        }
+        match self.source {
+            PaletteSource::Pywal => paths::cache_home().map(|dir| dir.join("wal/colors.json")),
+            _ => None,
+        }
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), paths::home_dir()) {
+        (Some(relative), Some(home)) => home.join(relative),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// CSS custom properties set on the document root by the frontend.
+#[derive(Debug, Serialize, Clone, Default, TS)]
+#[ts(export, export_to = "../../src/types/Palette.ts")]
+pub struct Palette {
+    /// property name, e.g. "--palette-color4", -> colour.
+    /// `--palette-background`, `--palette-foreground` and `--palette-accent`
+    /// alias the matching colour of each source
+    pub variables: BTreeMap<String, String>,
+}
+
+pub fn load_palette(config: &PaletteConfig) -> Result<Palette, String> {
+    let Some(path) = config.resolve_path() else {
+        return Ok(Palette::default());
+    };
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read palette {path:?}: {e}"))?;
+    let document = if content.trim_start().starts_with('{') {
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        parse_yaml_mappings(&content)
+    }
+    .map_err(|e| format!("Failed to parse palette {path:?}: {e}"))?;
+
+    let mut colors = BTreeMap::new();
+    // (background, foreground, accent) among the collected colours
+    let aliases = match config.source {
+        PaletteSource::None => return Ok(Palette::default()),
+        PaletteSource::Pywal => {
+            for group in ["special", "colors"] {
+                if let Some(group) = document.get(group) {
+                    collect_colors(group, "", &mut colors);
+                }
+            }
+            ["background", "foreground", "color4"]
+        }
+        PaletteSource::Base16 => {
+            // newer schemes nest the colours in `palette`
+            let scheme = document.get("palette").unwrap_or(&document);
+            collect_colors(scheme, "", &mut colors);
+            colors.retain(|key, _| is_base16_key(key));
+            ["base00", "base05", "base0d"]
+        }
+        PaletteSource::Matugen => {
+            let all_colors = document.get("colors").unwrap_or(&document);
+            let scheme = select_scheme(all_colors, config.scheme.key());
+            collect_colors(&scheme, "", &mut colors);
+            ["background", "on-background", "primary"]
+        }
+    };
+    if colors.is_empty() {
+        return Err(format!("No colours found in palette {path:?}"));
+    }
+
+    let mut variables: BTreeMap<String, String> = colors
+        .iter()
+        .map(|(key, color)| (format!("--palette-{key}"), color.clone()))
+        .collect();
+    for (alias, key) in ["background", "foreground", "accent"].iter().zip(aliases) {
+        if let Some(color) = colors.get(key) {
+            variables.insert(format!("--palette-{alias}"), color.clone());
+        }
+    }
+    Ok(Palette { variables })
+}
+
+// `matugen --json hex` nests the schemes as `colors.dark.primary`, newer versions
+// as `colors.primary.dark.color`; a template may write a single flat scheme
+fn select_scheme(colors: &Value, scheme: &str) -> Value {
+    if let Some(selected) = colors.get(scheme) {
+        return selected.clone();
+    }
+    let Value::Object(map) = colors else {
+        return colors.clone();
+    };
+    let selected = map
+        .iter()
+        .map(|(key, color)| {
+            let color = color.get(scheme).unwrap_or(color);
+            (key.clone(), color.get("color").unwrap_or(color).clone())
+        })
+        .collect();
+    Value::Object(selected)
+}
+
+// nested keys are joined with dashes, e.g. `primary: {dark: ...}` -> `primary-dark`
+fn collect_colors(value: &Value, prefix: &str, colors: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(map) => collect_object(map, prefix, colors),
+        Value::String(raw) => {
+            if let Some(color) = parse_color(raw) {
+                colors.insert(prefix.to_string(), color);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_object(map: &Map<String, Value>, prefix: &str, colors: &mut BTreeMap<String, String>) {
+    for (key, nested) in map {
+        let key = key.to_lowercase().replace(['_', '.', ' '], "-");
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{prefix}-{key}")
+        };
+        collect_colors(nested, &key, colors);
+    }
+}
+
+/// Reads the part of YAML that palette files use: mappings nested by indentation,
+/// plain or quoted scalars and `#` comments. Lists are skipped.
+fn parse_yaml_mappings(content: &str) -> Result<Value, String> {
+    // the open mappings with the indentation and name of their key, the root first
+    let mut open: Vec<(usize, String, Map<String, Value>)> = vec![(0, String::new(), Map::new())];
+
+    let is_comment = |before: &str, c| c == '#' && (before.is_empty() || before.ends_with(' '));
+
+    for (number, line) in content.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = find_unquoted(line, is_comment)
+            .map_or(line, |comment| &line[..comment])
+            .trim_end();
+        let entry = line.trim_start();
+        if entry.is_empty() || entry == "---" || entry == "..." || entry.starts_with('-') {
+            continue;
+        }
+        if line.starts_with('\t') {
+            return Err(format!(
+                "line {}: tabs can't be used for indentation",
+                number + 1
+            ));
+        }
+        let indent = line.len() - entry.len();
+        let colon = find_unquoted(entry, |_, c| c == ':')
+            .filter(|&colon| entry[colon + 1..].is_empty() || entry[colon + 1..].starts_with(' '))
+            .ok_or_else(|| format!("line {}: expected `key: value`", number + 1))?;
+        let key = unquote(entry[..colon].trim());
+        let value = entry[colon + 1..].trim();
+
+        while open.len() > 1 && indent <= open[open.len() - 1].0 {
+            close_mapping(&mut open);
+        }
+        if value.is_empty() {
+            open.push((indent, key, Map::new()));
+        } else {
+            let (_, _, mapping) = open.last_mut().expect("the root is never closed");
+            mapping.insert(key, Value::String(unquote(value)));
+        }
+    }
+
+    while open.len() > 1 {
+        close_mapping(&mut open);
+    }
+    let (_, _, root) = open.pop().expect("the root is never closed");
+    Ok(Value::Object(root))
+}
+
+fn close_mapping(open: &mut Vec<(usize, String, Map<String, Value>)>) {
+    if let Some((_, key, mapping)) = open.pop() {
+        if let Some((_, _, parent)) = open.last_mut() {
+            parent.insert(key, Value::Object(mapping));
+        }
+    }
+}
+
+// the first character outside of quotes `is_match` accepts, given the text before it;
+// a quote only opens at the start of a word, so `Chris's scheme` is plain text
+fn find_unquoted(text: &str, is_match: impl Fn(&str, char) -> bool) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        let before = &text[..i];
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'')
+                && (before.is_empty() || before.ends_with([' ', ':'])) =>
+            {
+                quote = Some(c)
+            }
+            None if is_match(before, c) => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+fn unquote(scalar: &str) -> String {
+    let quoted = |quote: char| {
+        scalar
+            .strip_prefix(quote)
+            .and_then(|scalar| scalar.strip_suffix(quote))
+    };
+    if let Some(inner) = quoted('"') {
+        inner.replace("\\\"", "\"").replace("\\\\", "\\")
+    } else if let Some(inner) = quoted('\'') {
+        inner.replace("''", "'")
+    } else {
+        scalar.to_string()
+    }
+}
+
+fn is_base16_key(key: &str) -> bool {
+    key.len() == 6 && key.starts_with("base") && key[4..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Accepts `#rgb`, `#rrggbb` (with optional alpha) and bare `rrggbb` as used by base16.
+/// Other strings, like the wallpaper path of pywal, are not colours.
+fn parse_color(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let (hex, has_hash) = match raw.strip_prefix('#') {
+        Some(hex) => (hex, true),
+        None => (raw, false),
+    };
+    let valid_length = if has_hash {
+        matches!(hex.len(), 3 | 4 | 6 | 8)
+    } else {
+        hex.len() == 6
+    };
+    if valid_length && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(format!("#{}", hex.to_lowercase()))
+    } else {
+        None
+    }
+}
+
+// replaced whenever the palette file in the config changes
+static PALETTE_WATCHER: Lazy<Mutex<Option<Debouncer<RecommendedWatcher>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+// pywal writes all its files at once, a short delay avoids reading them half written
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Emits `palette-changed` with the new palette whenever the palette file changes.
+/// Called again after the config changed, replacing the previous watcher.
+pub fn watch_palette(app_handle: tauri::AppHandle) {
+    let Ok(mut watcher_guard) = PALETTE_WATCHER.lock() else {
+        error!("Failed to lock palette watcher");
+        return;
+    };
+    *watcher_guard = None;
+
+    let config = match APP_CONFIG.lock() {
+        Ok(config_guard) => config_guard.palette.clone(),
+        Err(e) => {
+            error!("Failed to lock config: {e}");
+            return;
+        }
+    };
+    let Some(palette_path) = config.resolve_path() else {
+        return;
+    };
+    // the file itself is usually replaced rather than modified, so its directory is watched
+    let Some(palette_dir) = palette_path.parent().map(|dir| dir.to_path_buf()) else {
+        return;
+    };
+
+    let watched_path = palette_path.clone();
+    let on_change = move |result: DebounceEventResult| {
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => {
+                error!("Failed to watch palette: {e}");
+                return;
+            }
+        };
+        if !events.iter().any(|event| event.path == watched_path) {
+            return;
+        }
+        match load_palette(&config) {
+            Ok(palette) => {
+                #[cfg(debug_assertions)]
+                info!("Palette {watched_path:?} changed, reloading");
+                if let Err(e) = app_handle.emit("palette-changed", palette) {
+                    error!("Failed to notify the frontend about the palette change: {e}");
+                }
+            }
+            Err(e) => error!("{e}"),
+        }
+    };
+
+    let mut debouncer = match new_debouncer(RELOAD_DEBOUNCE, on_change) {
+        Ok(debouncer) => debouncer,
+        Err(e) => {
+            error!("Failed to create palette watcher: {e}");
+            return;
+        }
+    };
+    if let Err(e) = debouncer
+        .watcher()
+        .watch(&palette_dir, RecursiveMode::NonRecursive)
+    {
+        error!("Failed to watch palette directory {palette_dir:?}: {e}");
+        return;
+    }
+    info!("Watching palette {palette_path:?}");
+    *watcher_guard = Some(debouncer);
+}
+
+#[tauri::command]
+pub fn get_palette() -> Result<Palette, String> {
+    let config = APP_CONFIG
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {e}"))?
+        .palette
+        .clone();
+    load_palette(&config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_base16_schemes() {
+        let classic = "\
+# comment
+scheme: \"Tomorrow Night\"
+author: Chris's scheme # by Chris Kempson
+base00: \"1d1f21\"
+base0D: '81a2be'
+";
+        assert_eq!(
+            parse_yaml_mappings(classic),
+            Ok(json!({
+                "scheme": "Tomorrow Night",
+                "author": "Chris's scheme",
+                "base00": "1d1f21",
+                "base0D": "81a2be",
+            }))
+        );
+
+        let nested = "\
+system: \"base16\"
+name: \"Tomorrow Night\"
+palette:
+  base00: \"#1d1f21\" # background
+  base05: \"#c5c8c6\"
+
+variant: \"dark\"
+";
+        assert_eq!(
+            parse_yaml_mappings(nested),
+            Ok(json!({
+                "system": "base16",
+                "name": "Tomorrow Night",
+                "palette": { "base00": "#1d1f21", "base05": "#c5c8c6" },
+                "variant": "dark",
+            }))
+        );
+    }
+
+    #[test]
+    fn parses_deeper_nesting_and_skips_lists() {
+        let content =
+            "colors:\n  primary:\n    dark: \"#aabbcc\"\n  tags:\n    - a\n  surface: '#112233'\n";
+        assert_eq!(
+            parse_yaml_mappings(content),
+            Ok(json!({
+                "colors": {
+                    "primary": { "dark": "#aabbcc" },
+                    "tags": {},
+                    "surface": "#112233",
+                }
+            }))
+        );
+    }
+
+    #[test]
+    fn rejects_lines_without_a_key() {
+        assert!(parse_yaml_mappings("base00: \"1d1f21\"\njust text\n").is_err());
+        assert!(parse_yaml_mappings("palette:\n\tbase00: \"1d1f21\"\n").is_err());
+    }
+}
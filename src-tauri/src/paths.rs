@@ -0,0 +1,119 @@
+use log::warn;
+use once_cell::sync::OnceCell;
+use std::path::PathBuf;
+
+const APP_DIR_NAME: &str = "cyberdeck";
+
+/// Directories used instead of the XDG ones, from `--config-dir` and friends
+/// or the matching `CYBERDECK_*_DIR` variables, e.g. for tests or sandboxes.
+#[derive(Debug, Clone, Default)]
+pub struct PathOverrides {
+    pub config_dir: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub state_dir: Option<PathBuf>,
+    pub runtime_dir: Option<PathBuf>,
+    /// Used instead of `$XDG_DATA_HOME`, see `data_home`.
+    pub data_home: Option<PathBuf>,
+}
+
+static PATH_OVERRIDES: OnceCell<PathOverrides> = OnceCell::new();
+
+pub fn set_path_overrides(overrides: PathOverrides) {
+    if PATH_OVERRIDES.set(overrides).is_err() {
+        warn!("Path overrides were already set, ignoring");
+    }
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .filter(|home| home.is_absolute())
+}
+
+// the spec asks to ignore relative paths, like an unset variable
+fn xdg_home(variable: &str, fallback: &str) -> Option<PathBuf> {
+    match std::env::var_os(variable).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => home_dir().map(|home| home.join(fallback)),
+    }
+}
+
+/// `$XDG_CONFIG_HOME`, defaulting to `~/.config`.
+pub fn config_home() -> Option<PathBuf> {
+    xdg_home("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_CACHE_HOME`, defaulting to `~/.cache`.
+pub fn cache_home() -> Option<PathBuf> {
+    xdg_home("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$XDG_DATA_HOME`, defaulting to `~/.local/share`. Icons are looked up below it, and
+/// desktop files below it shadow the system ones.
+pub fn data_home() -> Option<PathBuf> {
+    PATH_OVERRIDES
+        .get()
+        .and_then(|overrides| overrides.data_home.clone())
+        .or_else(|| xdg_home("XDG_DATA_HOME", ".local/share"))
+}
+
+/// `$XDG_STATE_HOME`, defaulting to `~/.local/state`.
+pub fn state_home() -> Option<PathBuf> {
+    xdg_home("XDG_STATE_HOME", ".local/state")
+}
+
+/// `$XDG_RUNTIME_DIR`, which has no default.
+pub fn runtime_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
+
+fn app_dir(
+    select: impl Fn(&PathOverrides) -> Option<&PathBuf>,
+    base: Option<PathBuf>,
+) -> Option<PathBuf> {
+    PATH_OVERRIDES
+        .get()
+        .and_then(select)
+        .cloned()
+        .or_else(|| base.map(|dir| dir.join(APP_DIR_NAME)))
+}
+
+/// Config file, profiles and themes, `$XDG_CONFIG_HOME/cyberdeck`.
+pub fn config_dir() -> Option<PathBuf> {
+    app_dir(|overrides| overrides.config_dir.as_ref(), config_home())
+}
+
+/// Everything that can be regenerated, like the application index and
+/// rasterised icons, `$XDG_CACHE_HOME/cyberdeck`.
+pub fn cache_dir() -> Option<PathBuf> {
+    app_dir(|overrides| overrides.cache_dir.as_ref(), cache_home())
+}
+
+/// Data that can't be regenerated but isn't state, `$XDG_DATA_HOME/cyberdeck`.
+pub fn data_dir() -> Option<PathBuf> {
+    app_dir(|overrides| overrides.data_dir.as_ref(), data_home())
+}
+
+/// The launch history, `$XDG_STATE_HOME/cyberdeck`.
+pub fn state_dir() -> Option<PathBuf> {
+    app_dir(|overrides| overrides.state_dir.as_ref(), state_home())
+}
+
+/// Sockets and locks, `$XDG_RUNTIME_DIR/cyberdeck`, `None` when the variable isn't set.
+pub fn runtime_dir() -> Option<PathBuf> {
+    app_dir(|overrides| overrides.runtime_dir.as_ref(), runtime_home())
+}
+
+/// The directories Cyberdeck uses, by name.
+pub fn directories() -> Vec<(&'static str, Option<PathBuf>)> {
+    vec![
+        ("config", config_dir()),
+        ("cache", cache_dir()),
+        ("data", data_dir()),
+        ("state", state_dir()),
+        ("runtime", runtime_dir()),
+    ]
+}
@@ -0,0 +1,103 @@
+use crate::config_file;
+use crate::icon_cache;
+use crate::stable_hash::fnv1a;
+use crate::theme;
+#[cfg(debug_assertions)]
+use log::debug;
+use log::{error, warn};
+use std::path::PathBuf;
+
+/// Icon ids starting with this are generated placeholders, followed by the desktop file id.
+pub const PLACEHOLDER_PREFIX: &str = "placeholder:";
+
+// used when the active theme has no `placeholder.svg`
+const DEFAULT_TEMPLATE: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64" viewBox="0 0 64 64">
+  <rect width="64" height="64" rx="12" fill="{background}"/>
+  <text x="32" y="32" dy="0.35em" text-anchor="middle" font-family="sans-serif" font-size="26" font-weight="bold" fill="{foreground}">{initials}</text>
+</svg>
+"#;
+
+/// `$XDG_CACHE_HOME/cyberdeck/icons/placeholders`
+pub fn get_placeholder_dir() -> Option<PathBuf> {
+    icon_cache::get_icon_cache_dir().map(|dir| dir.join("placeholders"))
+}
+
+/// The placeholder file of an icon id, `None` if it isn't a placeholder id.
+pub fn placeholder_path(id: &str) -> Option<PathBuf> {
+    let app_id = id.strip_prefix(PLACEHOLDER_PREFIX)?;
+    get_placeholder_dir().map(|dir| dir.join(format!("{:016x}.svg", fnv1a(app_id.as_bytes()))))
+}
+
+/// The icon id of an application's placeholder, written by `create_placeholder`.
+pub fn placeholder_id(app_id: &str) -> String {
+    format!("{PLACEHOLDER_PREFIX}{app_id}")
+}
+
+/// Writes the placeholder of an application without an icon and returns its icon id.
+/// The initials come from `name`, the colour from a hash of `app_id`, so an
+/// application keeps its colour between scans and machines.
+///
+/// Themes can replace the SVG with a `placeholder.svg` in their directory, where
+/// `{initials}`, `{background}`, `{foreground}` and `{hue}` are substituted.
+pub fn create_placeholder(app_id: &str, name: &str) -> Option<String> {
+    let id = placeholder_id(app_id);
+    let path = placeholder_path(&id)?;
+
+    let template = theme::get_active_theme_dir()
+        .map(|dir| dir.join("placeholder.svg"))
+        .filter(|path| path.is_file())
+        .and_then(|path| match std::fs::read_to_string(&path) {
+            Ok(template) => Some(template),
+            Err(e) => {
+                warn!("Failed to read placeholder template {path:?}: {e}");
+                None
+            }
+        })
+        .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+    let hue = fnv1a(app_id.as_bytes()) % 360;
+    let svg = template
+        .replace("{initials}", &escape_xml(&initials(name)))
+        .replace("{background}", &format!("hsl({hue}, 55%, 42%)"))
+        .replace("{foreground}", "#ffffff")
+        .replace("{hue}", &hue.to_string());
+
+    // rewriting an unchanged placeholder would invalidate its rasterised copy
+    if std::fs::read_to_string(&path).is_ok_and(|existing| existing == svg) {
+        return Some(id);
+    }
+    if let Some(parent) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            error!("Failed to create placeholder directory {parent:?}: {e}");
+            return None;
+        }
+    }
+    if let Err(e) = config_file::write_atomically(&path, svg) {
+        error!("Failed to write placeholder {path:?}: {e}");
+        return None;
+    }
+    #[cfg(debug_assertions)]
+    debug!("Created placeholder icon for '{app_id}' at {path:?}");
+    Some(id)
+}
+
+// first letters of the first two words, e.g. "VNC Viewer" -> "VV", "firefox" -> "F"
+fn initials(name: &str) -> String {
+    let initials: String = name
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .take(2)
+        .flat_map(char::to_uppercase)
+        .collect();
+    if initials.is_empty() {
+        String::from("?")
+    } else {
+        initials
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
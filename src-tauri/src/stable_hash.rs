@@ -0,0 +1,64 @@
+/// 64-bit FNV-1a. Unlike `DefaultHasher` it gives the same hash in every build,
+/// so it can name files and go into caches that outlive an update.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Hashes several fields with FNV-1a, see `fnv1a`.
+pub struct StableHasher {
+    hash: u64,
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher {
+            hash: 0xcbf29ce484222325,
+        }
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash = (self.hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Adds a field followed by a separator, so `("ab", "c")` and `("a", "bc")` differ.
+    pub fn write_field(&mut self, field: impl AsRef<[u8]>) {
+        self.write(field.as_ref());
+        self.write(&[0xff]);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_the_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn fields_are_separated() {
+        let hash = |fields: &[&str]| {
+            let mut hasher = StableHasher::new();
+            fields.iter().for_each(|field| hasher.write_field(field));
+            hasher.finish()
+        };
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+    }
+}
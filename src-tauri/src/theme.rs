@@ -0,0 +1,135 @@
+use crate::config::APP_CONFIG;
+use crate::paths;
+use crate::protocol::{percent_decode, text_response};
+use log::{error, info, warn};
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::Emitter;
+
+/// Serves the files of the active theme, e.g. `cyberdeck-theme://localhost/theme.css`.
+pub const THEME_SCHEME: &str = "cyberdeck-theme";
+
+// editors often write a file in several steps, those are reported as one change
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// `~/.config/cyberdeck/themes`, every subdirectory is a theme named after it.
+pub fn get_themes_dir() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join("themes"))
+}
+
+/// Directory of the theme selected by `ui.theme`, `None` for the built-in look.
+pub fn get_active_theme_dir() -> Option<PathBuf> {
+    let theme = APP_CONFIG.lock().ok()?.ui.theme.clone();
+    if theme.is_empty() {
+        return None;
+    }
+    if !is_theme_name(&theme) {
+        warn!("Ignoring theme {theme:?}, it is not a directory name");
+        return None;
+    }
+    get_themes_dir().map(|dir| dir.join(theme))
+}
+
+/// Whether `name` is a single directory below `themes/`, so `../..` and the like
+/// can't select a directory outside of it.
+pub fn is_theme_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    !name.contains(['/', '\\'])
+        && matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+}
+
+/// Handler of the `cyberdeck-theme` protocol. Only files inside the active theme are served.
+pub fn handle_theme_request(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some(theme_dir) = get_active_theme_dir() else {
+        return text_response(StatusCode::NOT_FOUND, "no theme is selected");
+    };
+    let Some(relative_path) = percent_decode(request.uri().path()) else {
+        return text_response(StatusCode::BAD_REQUEST, "invalid path encoding");
+    };
+    let Some(file_path) = resolve_theme_file(&theme_dir, &relative_path) else {
+        return text_response(StatusCode::FORBIDDEN, "path leaves the theme directory");
+    };
+
+    match std::fs::read(&file_path) {
+        Ok(content) => {
+            let mime = mime_guess::from_path(&file_path).first_or_octet_stream();
+            Response::builder()
+                .header(header::CONTENT_TYPE, mime.essence_str())
+                // edits are picked up on reload instead of a stale cached copy
+                .header(header::CACHE_CONTROL, "no-cache")
+                .body(content)
+                .expect("Failed to build theme response")
+        }
+        Err(e) => {
+            warn!("Failed to read theme file {file_path:?}: {e}");
+            text_response(StatusCode::NOT_FOUND, "theme file not found")
+        }
+    }
+}
+
+// rejects `..`, absolute paths and the like, so requests can't escape the theme
+fn resolve_theme_file(theme_dir: &Path, relative_path: &str) -> Option<PathBuf> {
+    let relative_path = Path::new(relative_path.trim_start_matches('/'));
+    let is_contained = relative_path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !is_contained || relative_path.as_os_str().is_empty() {
+        return None;
+    }
+    Some(theme_dir.join(relative_path))
+}
+
+/// Emits `theme-changed` whenever a file of the active theme changes.
+/// The watcher stops when the returned value is dropped.
+pub fn watch_themes(app_handle: tauri::AppHandle) -> Option<Debouncer<RecommendedWatcher>> {
+    let themes_dir = get_themes_dir()?;
+    if let Err(e) = std::fs::create_dir_all(&themes_dir) {
+        error!("Failed to create themes directory {themes_dir:?}: {e}");
+        return None;
+    }
+
+    let on_change = move |result: DebounceEventResult| {
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => {
+                error!("Failed to watch themes: {e}");
+                return;
+            }
+        };
+        // the theme may have been switched since the watcher started
+        let Some(theme_dir) = get_active_theme_dir() else {
+            return;
+        };
+        if events
+            .iter()
+            .any(|event| event.path.starts_with(&theme_dir))
+        {
+            #[cfg(debug_assertions)]
+            info!("Theme {theme_dir:?} changed, reloading");
+            if let Err(e) = app_handle.emit("theme-changed", ()) {
+                error!("Failed to notify the frontend about the theme change: {e}");
+            }
+        }
+    };
+
+    let mut debouncer = match new_debouncer(RELOAD_DEBOUNCE, on_change) {
+        Ok(debouncer) => debouncer,
+        Err(e) => {
+            error!("Failed to create theme watcher: {e}");
+            return None;
+        }
+    };
+    if let Err(e) = debouncer
+        .watcher()
+        .watch(&themes_dir, RecursiveMode::Recursive)
+    {
+        error!("Failed to watch themes directory {themes_dir:?}: {e}");
+        return None;
+    }
+    info!("Watching themes in {themes_dir:?}");
+    Some(debouncer)
+}
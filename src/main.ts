@@ -1,7 +1,12 @@
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 import { convertFileSrc } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { ClientConfig } from "./types/ClientConfig";
 import { Application } from "./types/Application";
+import { ApplicationsDiff } from "./types/ApplicationsDiff";
+import { Action } from "./types/Action";
+import { Palette } from "./types/Palette";
+import { ScanEvent } from "./types/ScanEvent";
 import { EN_UA_TRANSLITERATOR } from "./utils/LayoutTransliterator";
 import { distance } from "fastest-levenshtein";
 
@@ -9,12 +14,14 @@ import { distance } from "fastest-levenshtein";
 let apps: Application[] = [];
 let appsEntries: HTMLDivElement[] = [];
 let availableApps: HTMLDivElement[] = [];
+let pendingDiffs: ApplicationsDiff[] | null = [];
 let config: ClientConfig;
 let maxPages: number;
 let currentPage = 0;
 let currentSelectedIdx = 0;
 const filter = document.getElementById("filter") as HTMLInputElement;
 const container = document.getElementById("app-list") as HTMLDivElement;
+const themeLink = document.getElementById("theme") as HTMLLinkElement;
 
 let isDev = false;
 (async () => {
@@ -22,29 +29,63 @@ let isDev = false;
     console.log("Development mode:", isDev);
 })();
 
+// the cached list right away, the backend rescans in the background
+// and sends what changed as `applications-changed`
 async function fetchApps() {
     console.log("fetching apps");
-
-    const apps = await tryGetCachedApplications();
+    const apps = await invoke<Application[] | null>("load_applications");
     if (apps) {
         console.log("get cached apps succesfully");
         return apps;
     }
 
-    console.log("cached apps not found, or error occured");
-    console.log("trying to get applications list");
-    const freshApps = await getApplications();
-    return freshApps;
+    console.log("cached apps not found, scanning");
+    return streamApps();
+}
+
+// resolves with the first batch so the first page shows up before the scan is
+// done, later batches are added like installed applications
+function streamApps(): Promise<Application[]> {
+    return new Promise((resolve, reject) => {
+        let resolved = false;
+        const onEvent = new Channel<ScanEvent>();
+        onEvent.onmessage = (message) => {
+            if (message.kind === "batch") {
+                if (resolved) {
+                    applyApplicationsDiff({ added: message.applications, updated: [], removed: [] });
+                } else {
+                    resolved = true;
+                    resolve(message.applications);
+                }
+                return;
+            }
+            console.log(`Scanned ${message.applications} apps in ${message.total_ms}ms`, message);
+            if (!resolved) {
+                resolved = true;
+                resolve([]);
+            }
+        };
+        invoke("stream_applications", { onEvent }).catch(reject);
+    });
 }
 
-async function tryGetCachedApplications() {
-    const apps = await invoke<Application[] | null>("try_get_cached_applications");
-    return apps;
+// scale factor of the window, set at startup and whenever the window moves to
+// another screen; the backend rasterises icons for `size * scale` pixels
+let iconScale = 1;
+
+// served by the backend, which only knows the icons of the listed applications
+function iconUrl(icon: string): string {
+    return `${convertFileSrc(icon, "cyberdeck-icon")}?size=${config.icon_size}&scale=${iconScale}`;
 }
 
-async function getApplications() {
-    const apps = await invoke<Application[] | null>("get_desktop_applications");
-    return apps;
+function reloadIcons() {
+    appsEntries.concat(availableApps).forEach(entry => {
+        const appIcon = entry.querySelector(".app-icon") as HTMLImageElement | null;
+        const app = apps.find(app => app.name === entry.id);
+        if (appIcon && app?.icon) {
+            appIcon.src = iconUrl(app.icon);
+        }
+    });
 }
 
 async function createAppsEntries() {
@@ -73,45 +114,76 @@ async function createAppsEntries() {
         }
     });
 
+    rebuildAppsEntries();
+}
 
-    apps.forEach((app: Application, index: number) => {
-        const entry = document.createElement("div");
-        entry.className = "entry";
-        entry.id = app.name;
+function rebuildAppsEntries() {
+    appsEntries = apps.map(createAppEntry);
+}
 
-        appsEntries.push(entry);
+function createAppEntry(app: Application, index: number): HTMLDivElement {
+    const entry = document.createElement("div");
+    entry.className = "entry";
+    entry.id = app.name;
+    entry.dataset.searchKey = app.search_key;
+
+    // smth like rarity in cyberpunk
+    // TODO later
+    const appType = document.createElement("div");
+    appType.className = "app-type";
+    entry.appendChild(appType);
+
+    const appInfo = document.createElement("div");
+    appInfo.className = "app-info";
+    entry.appendChild(appInfo);
+
+    const appIcon = document.createElement("img");
+    if (app.icon) {
+        appIcon.src = iconUrl(app.icon);
+    }
+    appIcon.className = "app-icon";
+    // generated initials, themes can style them apart from real icons
+    appIcon.classList.toggle("placeholder", app.icon.startsWith("placeholder:"));
+    appInfo.appendChild(appIcon);
 
-        // smth like rarity in cyberpunk
-        // TODO later
-        const appType = document.createElement("div");
-        appType.className = "app-type";
-        entry.appendChild(appType);
+    if (isDev) {
+        const elementIdx = document.createElement("div");
+        elementIdx.textContent = `[${index}] `;
+        appInfo.appendChild(elementIdx);
+    }
 
-        const appInfo = document.createElement("div");
-        appInfo.className = "app-info";
-        entry.appendChild(appInfo);
+    const appName = document.createElement("div");
+    appName.className = "app-name";
+    appName.textContent = app.name;
+    appInfo.appendChild(appName);
 
-        const appIcon = document.createElement("img");
-        appIcon.src = convertFileSrc(app.icon);
-        appIcon.className = "app-icon";
-        appInfo.appendChild(appIcon);
+    return entry;
+}
 
-        if (isDev) {
-            const elementIdx = document.createElement("div");
-            elementIdx.textContent = `[${index}] `;
-            appInfo.appendChild(elementIdx);
-        }
+// applications installed or removed while the launcher is open
+function applyApplicationsDiff(diff: ApplicationsDiff) {
+    // the rescan may finish before the list is loaded, it is applied afterwards then
+    if (pendingDiffs) {
+        pendingDiffs.push(diff);
+        return;
+    }
+    apps = mergeApplicationsDiff(apps, diff);
+    rebuildAppsEntries();
+    filterApps();
+}
 
-        const appName = document.createElement("div");
-        appName.className = "app-name";
-        appName.textContent = app.name;
-        appInfo.appendChild(appName);
-    })
+function mergeApplicationsDiff(apps: Application[], diff: ApplicationsDiff): Application[] {
+    const removed = new Set(diff.removed.concat(diff.added.map(app => app.id)));
+    const updated = new Map(diff.updated.map(app => [app.id, app]));
+    return apps
+        .filter(app => !removed.has(app.id))
+        .map(app => updated.get(app.id) ?? app)
+        .concat(diff.added);
 }
 
 function selectAppByIdx(idx: number) {
-    availableApps[currentSelectedIdx].classList.remove("selected");
-    availableApps[idx].classList.add("selected");
+    availableApps[currentSelectedIdx]?.classList.remove("selected");
+    availableApps[idx]?.classList.add("selected");
     currentSelectedIdx = idx;
 }
 
@@ -148,8 +220,54 @@ function prevPage() {
     setPage((currentPage - 1 + maxPages) % maxPages);
 }
 
+// punctuation by its position on a US keyboard, see `keyFromEvent`
+const PUNCTUATION_CODES: Record<string, string> = {
+    Minus: "-", Equal: "=", BracketLeft: "[", BracketRight: "]", Backslash: "\\",
+    Semicolon: ";", Quote: "'", Comma: ",", Period: ".", Slash: "/", Backquote: "`",
+};
+
+// the unshifted character of the physical key, so Shift+1 is "1" rather than "!"
+// and bindings keep working with other layouts, e.g. Ctrl+j on a Ukrainian one
+function keyFromEvent(e: KeyboardEvent): string {
+    if (e.code.startsWith("Key")) return e.code.slice(3).toLowerCase();
+    if (e.code.startsWith("Digit")) return e.code.slice(5);
+    if (e.code in PUNCTUATION_CODES) return PUNCTUATION_CODES[e.code];
+    if (e.key === " ") return "Space";
+    return e.key.length === 1 ? e.key.toLowerCase() : e.key;
+}
+
+// builds the same canonical chord as `parse_chord` in the backend, e.g. "Ctrl+Shift+k"
+function chordFromEvent(e: KeyboardEvent): string {
+    const parts: string[] = [];
+    if (e.ctrlKey) parts.push("Ctrl");
+    if (e.altKey) parts.push("Alt");
+    if (e.shiftKey) parts.push("Shift");
+    if (e.metaKey) parts.push("Super");
+    parts.push(keyFromEvent(e));
+    return parts.join("+");
+}
+
+function actionFromEvent(e: KeyboardEvent): Action | undefined {
+    return config.keybindings[chordFromEvent(e)];
+}
+
 async function addAppSelection() {
     document.addEventListener("keydown", (e) => {
+        const action = actionFromEvent(e);
+        if (action === undefined) {
+            return;
+        }
+        // also keeps bound keys, like the arrows, from moving the cursor in the filter input
+        e.preventDefault();
+
+        if (action === "hide") {
+            invoke("exit").then(() => console.log("Exiting app"));
+            return;
+        }
+
+        if (availableApps.length === 0) {
+            return;
+        }
 
         const selected = document.querySelector(".entry.selected") as HTMLDivElement;
         if (selected === null) {
@@ -166,9 +284,8 @@ async function addAppSelection() {
         // if scroll by ArrowRight selected item also moves itself from existence
 
         // outside of switch because of block scope
-        switch (e.key) {
-            case "ArrowUp":
-                e.preventDefault();
+        switch (action) {
+            case "prev":
                 newSelectedIndex -= 1;
                 if (newSelectedIndex < currentPage * config.apps_per_page) {
                     prevPage();
@@ -178,8 +295,7 @@ async function addAppSelection() {
                 }
                 selectAppByIdx(newSelectedIndex);
                 break;
-            case "ArrowDown":
-                e.preventDefault();
+            case "next":
                 newSelectedIndex += 1;
                 const lastIdxOnPage = currentPage * config.apps_per_page + config.apps_per_page - 1;
                 if (newSelectedIndex > lastIdxOnPage || newSelectedIndex >= availableApps.length) {
@@ -190,8 +306,7 @@ async function addAppSelection() {
                 }
                 selectAppByIdx(newSelectedIndex);
                 break;
-            case "ArrowLeft":
-                e.preventDefault();
+            case "prev_page":
                 newSelectedIndex = currentSelectedIdx - config.apps_per_page;
                 if (newSelectedIndex < 0) {
                     newSelectedIndex = (maxPages - 1) * config.apps_per_page + currentSelectedIdx;
@@ -202,8 +317,7 @@ async function addAppSelection() {
                 prevPage();
                 selectAppByIdx(newSelectedIndex);
                 break;
-            case "ArrowRight":
-                e.preventDefault();
+            case "next_page":
                 newSelectedIndex = currentSelectedIdx + config.apps_per_page;
                 // used to determine if we need to shift index when moving to first page
                 const isEvenlyDivisible = availableApps.length % config.apps_per_page === 0;
@@ -227,12 +341,16 @@ async function addAppSelection() {
                 selectAppByIdx(newSelectedIndex);
                 nextPage();
                 break;
-            case "Enter":
+            case "launch":
+            case "launch_in_terminal":
                 const appName = selected.querySelector(".app-name")?.textContent;
                 if (appName) {
-                    runApp(appName);
+                    runApp(appName, action === "launch_in_terminal");
                 }
                 break;
+            default:
+                console.log("Action is not supported yet:", action);
+                break;
         }
     })
 
@@ -248,7 +366,7 @@ function filterApps() {
     let app, appName;
     appsEntries.forEach(entry => {
         app = entry.querySelector(".app-name") as HTMLDivElement;
-        appName = app.innerText.toLowerCase();
+        appName = entry.dataset.searchKey ?? app.innerText.toLowerCase();
 
         if (appName.indexOf(filterText) > -1 || appName.indexOf(transliteratedText) > -1) {
             availableApps.push(entry.cloneNode(true) as HTMLDivElement);
@@ -264,29 +382,78 @@ function filterApps() {
 
         return distance(leftAppName, transliteratedText) * startWithLeft - distance(rightAppName, transliteratedText) * startWithRight;
     });
+    applyResultLimit();
     maxPages = Math.ceil(availableApps.length / config.apps_per_page);
     setPage(0);
     selectAppByIdx(0);
 }
 
-function runApp(appName: string) {
+function applyResultLimit() {
+    if (config.result_limit > 0 && availableApps.length > config.result_limit) {
+        availableApps.length = config.result_limit;
+    }
+}
+
+function applyClientConfig() {
+    filter.placeholder = config.placeholder;
+    container.classList.toggle("grid", config.layout === "grid");
+    container.style.setProperty("--grid-columns", String(config.grid_columns));
+    document.body.classList.toggle("no-animations", !config.animations);
+    applyTheme();
+}
+
+// served by the backend from `themes/<name>/` of the config directory
+function applyTheme() {
+    if (!config.theme) {
+        themeLink.removeAttribute("href");
+        return;
+    }
+    // the query only changes the URL, so the webview fetches the edited file again
+    themeLink.href = `${convertFileSrc("theme.css", "cyberdeck-theme")}?v=${Date.now()}`;
+}
+
+// names of the --palette-* variables currently set on the root element
+let paletteVariables: string[] = [];
+
+function applyPalette(palette: Palette) {
+    const root = document.documentElement;
+    paletteVariables.forEach(name => root.style.removeProperty(name));
+    paletteVariables = Object.keys(palette.variables);
+    for (const [name, color] of Object.entries(palette.variables)) {
+        root.style.setProperty(name, color!);
+    }
+}
+
+async function loadPalette() {
+    try {
+        applyPalette(await invoke<Palette>("get_palette"));
+    } catch (e) {
+        console.log("Failed to load palette:", e);
+    }
+}
+
+function afterLaunch() {
+    // the backend exits the app unless it should stay open
+    if (config.keep_open_after_launch) {
+        filter.value = "";
+        filterApps();
+        filter.focus();
+    }
+}
+
+function runApp(appName: string, inTerminal = false) {
     let app = apps.find(app => app.name == appName);
     if (app) {
         console.log("Running {} with command: {}", appName, app.exec);
-        invoke("start_program", { "exec": app.exec });
+        // the exec of `Terminal=true` applications already starts the terminal
+        inTerminal = inTerminal && !app.terminal;
+        invoke("start_program", { "exec": app.exec, "inTerminal": inTerminal, "appId": app.id }).then(afterLaunch);
     }
 }
 
-window.addEventListener("keydown", (event) => {
-    if (event.key === "Escape") {
-        invoke("exit").then(() => console.log("Exiting app"));
-    }
-});
-
-// disable arrow key actions for the filter input
-filter.addEventListener("keydown", (e) => {
-    if (["ArrowUp", "ArrowDown", "ArrowLeft", "ArrowRight"].includes(e.key)) {
-        e.preventDefault();
+window.addEventListener("blur", () => {
+    if (config?.close_on_focus_loss) {
+        invoke("exit").then(() => console.log("Exiting app after losing focus"));
     }
 });
 
@@ -295,13 +462,51 @@ async function main() {
         config = client_config;
         console.log("Config loaded: ", config);
     });
+    iconScale = await invoke<number>("get_icon_scale");
+    applyClientConfig();
+    await loadPalette();
+    await addAppSelection();
+
+    // emitted when a file of the active theme was edited
+    await listen("theme-changed", () => {
+        console.log("Theme changed, reloading");
+        applyTheme();
+    });
+
+    // emitted when the palette file was rewritten, e.g. by pywal after a wallpaper change
+    await listen<Palette>("palette-changed", (event) => {
+        console.log("Palette changed, reloading");
+        applyPalette(event.payload);
+    });
+
+    // emitted when the window moved to a screen with another scale factor
+    await listen<number>("icon-scale-changed", (event) => {
+        iconScale = event.payload;
+        reloadIcons();
+    });
+
+    // emitted after `set_cyberdeck_config` saved new settings
+    await listen("config-changed", async () => {
+        config = await invoke<ClientConfig>("get_client_config");
+        console.log("Config reloaded: ", config);
+        applyClientConfig();
+        await loadPalette();
+        reloadIcons();
+        if (appsEntries.length > 0) {
+            filterApps();
+        }
+    });
+
+    await listen<ApplicationsDiff>("applications-changed", (event) => {
+        console.log("Applications changed: ", event.payload);
+        applyApplicationsDiff(event.payload);
+    });
 
     const fetchedApps = await fetchApps();
-    if (fetchedApps) {
-        apps = fetchedApps;
-    } else {
-        return;
-    }
+    apps = (pendingDiffs ?? []).reduce(mergeApplicationsDiff, fetchedApps);
+    pendingDiffs = null;
+    // the most used applications first, the rest keeps the order of the index
+    apps.sort((a, b) => b.frecency - a.frecency);
 
     if (apps.length === 0) {
         // TODO show that apps not found
@@ -309,8 +514,6 @@ async function main() {
         return;
     }
 
-    maxPages = Math.ceil(apps.length / config.apps_per_page);
-
     await createAppsEntries();
 
     if (appsEntries.length == 0) {
@@ -319,6 +522,8 @@ async function main() {
     }
 
     availableApps = appsEntries.map(entry => entry.cloneNode(true) as HTMLDivElement);
+    applyResultLimit();
+    maxPages = Math.ceil(availableApps.length / config.apps_per_page);
     availableApps[0].classList.add("selected");
     setPage(0);
 
@@ -328,11 +533,6 @@ async function main() {
     if (isDev) {
         console.log(apps);
     }
-
-    await addAppSelection();
-    // TODO remove 
-    // updating cache
-    await getApplications();
 }
 
 main();
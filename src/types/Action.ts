@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Action = "next" | "prev" | "next_page" | "prev_page" | "launch" | "launch_in_terminal" | "hide" | "unbound";
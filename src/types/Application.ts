@@ -1,3 +1,39 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type Application = { name: string, comment: string, icon: string, exec: string, };
+export type Application = { 
+/**
+ * path of the desktop file, identifies the application in `ApplicationsDiff`
+ */
+id: string, name: string, comment: string, 
+/**
+ * `Icon` of the desktop entry, served through the `cyberdeck-icon` protocol.
+ * A generated placeholder (`placeholder:<desktop file id>`) when the icon couldn't be found
+ */
+icon: string, 
+/**
+ * the file `icon` is served from, rasterised into the icon cache by the scan;
+ * empty when the placeholder couldn't be written either
+ */
+icon_path: string, exec: string, 
+/**
+ * `Categories` of the desktop entry, e.g. `["Development", "IDE"]`
+ */
+categories: Array<string>, 
+/**
+ * runs in `terminal_app`, `Terminal=true` in the desktop entry
+ */
+terminal: boolean, 
+/**
+ * `TryExec` of the desktop entry, only listed while this program is installed.
+ * Checked on every scan, installing it doesn't touch the desktop file
+ */
+try_exec: string | null, 
+/**
+ * what the search matches against: the lowercase name, generic name, keywords
+ * and command line, one per line so a query can't match across them
+ */
+search_key: string, 
+/**
+ * how often and recently it was launched, filled in from the index
+ */
+frecency: number, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Application } from "./Application";
+
+/**
+ * Payload of `applications-changed`, applications are matched by `id`.
+ */
+export type ApplicationsDiff = { added: Array<Application>, updated: Array<Application>, 
+/**
+ * ids of the applications that are gone or hidden now
+ */
+removed: Array<string>, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CacheFormat } from "./CacheFormat";
+
+export type CacheConfig = { 
+/**
+ * Format the application index is stored in
+ */
+format: CacheFormat, };
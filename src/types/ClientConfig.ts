@@ -1,3 +1,25 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Action } from "./Action";
+import type { Layout } from "./Layout";
+import type { Mode } from "./Mode";
 
-export type ClientConfig = { apps_per_page: number, };
+/**
+ * The part of the config the frontend needs, resolved for the current layout.
+ */
+export type ClientConfig = { 
+/**
+ * applications on a single page of the current layout
+ */
+apps_per_page: number, layout: Layout, grid_columns: number, result_limit: number, placeholder: string, default_mode: Mode, close_on_focus_loss: boolean, keep_open_after_launch: boolean, animations: boolean, 
+/**
+ * requested from the `cyberdeck-icon` protocol
+ */
+icon_size: number, 
+/**
+ * active theme, its files are served through the `cyberdeck-theme` protocol
+ */
+theme: string, 
+/**
+ * canonical key chord, e.g. "Ctrl+Shift+k", -> action
+ */
+keybindings: { [key in string]?: Action }, };
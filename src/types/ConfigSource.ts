@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where a config value came from, from the lowest to the highest precedence.
+ */
+export type ConfigSource = { "kind": "default" } | { "kind": "system", path: string, } | { "kind": "user", path: string, } | { "kind": "profile", name: string, path: string, } | { "kind": "env", variable: string, } | { "kind": "cli", argument: string, };
@@ -0,0 +1,44 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CacheConfig } from "./CacheConfig";
+import type { KeybindingsConfig } from "./KeybindingsConfig";
+import type { LookupDirs } from "./LookupDirs";
+import type { PaletteConfig } from "./PaletteConfig";
+import type { UiConfig } from "./UiConfig";
+
+export type CyberdeckConfig = { 
+/**
+ * Schema version of the config file, used to migrate it after updates
+ */
+version: number, 
+/**
+ * Terminal that runs applications with `Terminal=true`
+ */
+terminal_app: string, 
+/**
+ * Compared against `OnlyShowIn` and `NotShowIn` of desktop entries
+ */
+desktop_environment: string, 
+/**
+ * Icon theme to look icons up in, empty to detect it from the GTK, GNOME or KDE settings
+ */
+icon_theme: string, 
+/**
+ * Directories scanned recursively for .desktop files
+ */
+lookup_dirs: LookupDirs, 
+/**
+ * Look and behaviour of the launcher window
+ */
+ui: UiConfig, 
+/**
+ * Key chords mapped to launcher actions
+ */
+keybindings: KeybindingsConfig, 
+/**
+ * Colours generated from the wallpaper, exposed as CSS variables
+ */
+palette: PaletteConfig, 
+/**
+ * How the application index is stored between runs
+ */
+cache: CacheConfig, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DesktopFileStatus } from "./DesktopFileStatus";
+
+export type DesktopFileReport = { path: string, 
+/**
+ * what shadowing compares, e.g. `kde-konsole.desktop`
+ */
+desktop_file_id: string, status: DesktopFileStatus, };
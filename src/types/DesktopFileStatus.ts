@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IconResolution } from "./IconResolution";
+import type { SkipReason } from "./SkipReason";
+
+export type DesktopFileStatus = { "kind": "listed", name: string, icon: IconResolution, } | { "kind": "skipped", reason: SkipReason, };
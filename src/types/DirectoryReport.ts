@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type DirectoryReport = { 
+/**
+ * e.g. `cache`
+ */
+name: string, 
+/**
+ * `None` when neither its XDG variable nor HOME is set
+ */
+path: string | null, exists: boolean, };
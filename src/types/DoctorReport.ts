@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DesktopFileReport } from "./DesktopFileReport";
+import type { DirectoryReport } from "./DirectoryReport";
+import type { EffectiveConfig } from "./EffectiveConfig";
+import type { LookupDirReport } from "./LookupDirReport";
+
+/**
+ * Why applications are or aren't listed, for `cyberdeck doctor`.
+ */
+export type DoctorReport = { version: string, config: EffectiveConfig, directories: Array<DirectoryReport>, lookup_dirs: Array<LookupDirReport>, 
+/**
+ * the configured icon theme, or the one detected from the desktop settings
+ */
+icon_theme: string, scale_factor: number, 
+/**
+ * every desktop file below the lookup directories, in the order they are listed
+ */
+desktop_files: Array<DesktopFileReport>, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConfigSource } from "./ConfigSource";
+import type { CyberdeckConfig } from "./CyberdeckConfig";
+
+export type EffectiveConfig = { config: CyberdeckConfig, 
+/**
+ * dotted key path -> where its value came from
+ */
+sources: { [key in string]?: ConfigSource }, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Which icon file was chosen for an application.
+ */
+export type IconResolution = { "kind": "found", icon: string, path: string, } | { "kind": "missing", placeholder: string, } | { "kind": "not_found", icon: string, icon_theme: string, placeholder: string, };
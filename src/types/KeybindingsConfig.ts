@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Action } from "./Action";
+import type { KeybindingsPreset } from "./KeybindingsPreset";
+
+export type KeybindingsConfig = { 
+/**
+ * Built-in bindings the custom ones are applied on top of
+ */
+preset: KeybindingsPreset, 
+/**
+ * Key chord, e.g. "Ctrl+Shift+k", -> action
+ */
+bindings: { [key in string]?: Action }, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type LookupDirs = { 
+/**
+ * searched in addition to the default directories
+ */
+extend: Array<string>, 
+/**
+ * replaces the default directories when not empty
+ */
+override: Array<string>, };
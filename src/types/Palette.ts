@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * CSS custom properties set on the document root by the frontend.
+ */
+export type Palette = { 
+/**
+ * property name, e.g. "--palette-color4", -> colour.
+ * `--palette-background`, `--palette-foreground` and `--palette-accent`
+ * alias the matching colour of each source
+ */
+variables: { [key in string]?: string }, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PaletteScheme } from "./PaletteScheme";
+import type { PaletteSource } from "./PaletteSource";
+
+export type PaletteConfig = { 
+/**
+ * Format of the palette file
+ */
+source: PaletteSource, 
+/**
+ * YAML or JSON palette file, `~/` is expanded.
+ * Optional for pywal, which defaults to `~/.cache/wal/colors.json`
+ */
+path: string, 
+/**
+ * Which colours to use from a matugen palette, which has a dark and a light scheme
+ */
+scheme: PaletteScheme, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Application } from "./Application";
+
+/**
+ * Sent through the channel of the `stream_applications` command.
+ */
+export type ScanEvent = { "kind": "batch", applications: Array<Application>, } | { "kind": "finished", applications: number, added: number, updated: number, removed: number, unchanged: number, walk_ms: number, parse_ms: number, total_ms: number, };
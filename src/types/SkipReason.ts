@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why a desktop file isn't listed.
+ */
+export type SkipReason = { "kind": "parse_error", message: string, } | { "kind": "not_an_application" } | { "kind": "no_exec" } | { "kind": "hidden" } | { "kind": "no_display" } | { "kind": "only_show_in", desktop_environment: string, } | { "kind": "not_show_in", desktop_environment: string, } | { "kind": "try_exec", program: string, } | { "kind": "shadowed", by: string, };
@@ -0,0 +1,50 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Layout } from "./Layout";
+import type { Mode } from "./Mode";
+
+export type UiConfig = { 
+/**
+ * Number of applications shown on a single page of the list layout
+ */
+apps_per_page: number, layout: Layout, 
+/**
+ * Columns of the grid layout
+ */
+grid_columns: number, 
+/**
+ * Rows of the grid layout
+ */
+grid_rows: number, 
+/**
+ * Maximum number of search results, 0 for no limit
+ */
+result_limit: number, 
+/**
+ * Text shown in the empty search field
+ */
+placeholder: string, 
+/**
+ * Mode the launcher opens in
+ */
+default_mode: Mode, 
+/**
+ * Close the launcher when it loses keyboard focus
+ */
+close_on_focus_loss: boolean, 
+/**
+ * Keep the launcher open after starting an application
+ */
+keep_open_after_launch: boolean, 
+/**
+ * Animate selection and page changes
+ */
+animations: boolean, 
+/**
+ * Icon size in logical pixels, multiplied by the screen's scale factor
+ */
+icon_size: number, 
+/**
+ * Directory in `themes/` next to the user config loaded on top of the built-in style,
+ * empty for none
+ */
+theme: string, };